@@ -1,6 +1,7 @@
 use std::fmt::{Debug, Display, Formatter};
 use thiserror::Error;
 use crate::iter::Offset;
+use crate::pattern::Kind;
 use crate::ser;
 
 #[derive(Debug)]
@@ -49,6 +50,8 @@ pub enum ErrorKind {
     UnclosedGroup,
     #[error("literal not closed")]
     UnclosedLiteral,
+    #[error("code block not closed")]
+    UnclosedCode,
     #[error("close character mismatched")]
     CloserMismatched,
     #[error("invalid quantifier sequence")]
@@ -63,6 +66,56 @@ pub enum ErrorKind {
     InvalidRuleHead,
     #[error("name cannot be empty")]
     EmptyName,
+    #[error("grammar has no rule")]
+    EmptyGrammar,
+    #[error("rule '{0}' is already defined")]
+    DuplicateRule(String),
+    #[error("rule '{0}' is not defined")]
+    UndefinedRule(String),
+    #[error("rule '{0}' is a fragment, which cannot be used in parser rule")]
+    FragmentReference(String),
+    #[error("rule '{0}' is skipped or hidden, which cannot be used in parser rule")]
+    TriviaReference(String),
+    #[error("{0} rule is only allowed in lexer")]
+    LexerOnlyRule(Kind),
+    #[error("mode '{0}' is not defined")]
+    UndefinedMode(String),
+    #[error("lexer rule refers to itself: {0}")]
+    RecursiveRule(String),
+    #[error("pattern is not supported in parser rule")]
+    UnsupportedPattern,
+    #[error("unknown directive '%{0}'")]
+    UnknownDirective(String),
+    #[error("directive is not allowed here")]
+    UnexpectedDirective,
+    #[error("precedence of '{0}' is already declared")]
+    DuplicatePrecedence(String),
+    #[error("precedence of '{0}' is not declared")]
+    UndefinedPrecedence(String),
+    #[error("capture '{0}' refers to different rules")]
+    CaptureMismatch(String),
+    #[error("code block is only allowed at end of top-level alternative of parser rule")]
+    UnexpectedCode,
+    #[error("type of rule '{0}' is already declared")]
+    DuplicateType(String),
+    #[error("alternative of rule '{0}' has no code, while rule has type")]
+    MissingCode(String),
+    #[error("capture '{0}' is not defined in alternative")]
+    UndefinedCapture(String),
+    #[error("shift/reduce conflict in state {state} on {lookahead}:{items}{example}")]
+    ShiftReduceConflict {
+        state: usize,
+        lookahead: String,
+        items: String,
+        example: String,
+    },
+    #[error("reduce/reduce conflict in state {state} on {lookahead}:{items}{example}")]
+    ReduceReduceConflict {
+        state: usize,
+        lookahead: String,
+        items: String,
+        example: String,
+    },
 }
 
 impl ErrorKind {
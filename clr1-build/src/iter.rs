@@ -47,7 +47,7 @@ impl Pred for RangeInclusive<char> {
 
 impl Pred for [char] {
     fn contains(&self, ch: char) -> bool {
-        self.iter().any(|&item| item == ch)
+        self.contains(&ch)
     }
 }
 
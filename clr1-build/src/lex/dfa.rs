@@ -0,0 +1,327 @@
+use crate::lex::nfa::{Nfa, Range, MAX};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+
+#[derive(Serialize)]
+pub struct Transition {
+    range: RangeInclusive<char>,
+    target: usize,
+}
+
+// minimal DFA of all lexer rules; mirrors `clr1::lex::Dfa`.
+// state 0 is start; missing transition means no longer match is possible.
+#[derive(Serialize)]
+pub struct Dfa {
+    // transitions of state i are `transitions[offsets[i]..offsets[i + 1]]`, sorted by range
+    offsets: Vec<usize>,
+    transitions: Vec<Transition>,
+    // index of rule matched on reaching state; earlier rule wins on same input
+    accept: Vec<Option<usize>>,
+}
+
+impl Dfa {
+    pub fn new(nfa: &Nfa) -> Self {
+        let intervals = intervals(nfa);
+        let (table, accept) = subset(nfa, &intervals);
+        let (table, accept) = minimize(&table, &accept);
+
+        let mut offsets = vec![0];
+        let mut transitions = Vec::new();
+        for row in &table {
+            // adjacent intervals to same target become one range
+            let mut i = 0;
+            while i < row.len() {
+                let Some(target) = row[i] else {
+                    i += 1;
+                    continue;
+                };
+
+                let start = intervals[i].0;
+                while i + 1 < row.len() && row[i + 1] == Some(target) && intervals[i + 1].0 == intervals[i].1 + 1 {
+                    i += 1;
+                }
+                let end = intervals[i].1;
+                i += 1;
+
+                if let Some(range) = chars(start, end) {
+                    transitions.push(Transition { range, target });
+                }
+            }
+            offsets.push(transitions.len());
+        }
+
+        Self {
+            offsets,
+            transitions,
+            accept,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn states(&self) -> usize {
+        self.accept.len()
+    }
+
+    // longest match as [rule index, length]
+    #[cfg(test)]
+    pub fn scan(&self, from: &str) -> Option<(usize, usize)> {
+        let mut state = 0;
+        let mut last = None;
+        for (i, ch) in from.char_indices() {
+            let transitions = &self.transitions[self.offsets[state]..self.offsets[state + 1]];
+            let Some(transition) = transitions.iter().find(|t| t.range.contains(&ch)) else {
+                break;
+            };
+
+            state = transition.target;
+            if let Some(rule) = self.accept[state] {
+                last = Some((rule, i + ch.len_utf8()));
+            }
+        }
+
+        last
+    }
+}
+
+// code points split at every boundary of every edge, so each edge covers whole intervals only.
+// intervals no edge covers are left out.
+fn intervals(nfa: &Nfa) -> Vec<Range> {
+    let mut bounds = BTreeSet::new();
+    for state in nfa.states() {
+        for (ranges, _) in state.edges() {
+            for &(start, end) in ranges {
+                bounds.insert(start);
+                bounds.insert(end + 1);
+            }
+        }
+    }
+
+    let bounds = bounds.into_iter().collect::<Vec<_>>();
+    let intervals = bounds.windows(2).map(|pair| (pair[0], pair[1] - 1));
+    intervals
+        .filter(|&(start, _)| {
+            nfa.states().iter().any(|state| {
+                let mut ranges = state.edges().iter().flat_map(|(ranges, _)| ranges);
+                ranges.any(|&(s, e)| s <= start && start <= e)
+            })
+        })
+        .collect()
+}
+
+fn closure(nfa: &Nfa, set: &mut BTreeSet<usize>) {
+    let mut stack = set.iter().copied().collect::<Vec<_>>();
+    while let Some(state) = stack.pop() {
+        for &next in nfa.states()[state].epsilon() {
+            if set.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+}
+
+// subset construction; [state][interval] -> state
+fn subset(nfa: &Nfa, intervals: &[Range]) -> (Vec<Vec<Option<usize>>>, Vec<Option<usize>>) {
+    let mut start = BTreeSet::from([0]);
+    closure(nfa, &mut start);
+
+    let mut sets = vec![start.clone()];
+    let mut index = HashMap::from([(start, 0)]);
+    let mut table = Vec::new();
+
+    let mut i = 0;
+    while i < sets.len() {
+        let mut targets = vec![BTreeSet::new(); intervals.len()];
+        for &state in &sets[i] {
+            for (ranges, target) in nfa.states()[state].edges() {
+                for &(start, end) in ranges {
+                    let from = intervals.partition_point(|&(_, e)| e < start);
+                    let to = intervals.partition_point(|&(s, _)| s <= end);
+                    for set in &mut targets[from..to] {
+                        set.insert(*target);
+                    }
+                }
+            }
+        }
+
+        let row = targets
+            .into_iter()
+            .map(|mut set| {
+                if set.is_empty() {
+                    return None;
+                }
+                closure(nfa, &mut set);
+
+                let next = sets.len();
+                let target = *index.entry(set.clone()).or_insert(next);
+                if target == next {
+                    sets.push(set);
+                }
+                Some(target)
+            })
+            .collect();
+        table.push(row);
+
+        i += 1;
+    }
+
+    let accept = sets
+        .iter()
+        .map(|set| set.iter().filter_map(|&state| nfa.states()[state].accept()).min())
+        .collect();
+
+    (table, accept)
+}
+
+// Hopcroft's algorithm, with missing transitions going to implicit dead state
+fn minimize(table: &[Vec<Option<usize>>], accept: &[Option<usize>]) -> (Vec<Vec<Option<usize>>>, Vec<Option<usize>>) {
+    let dead = table.len();
+    let symbols = table.first().map_or(0, |row| row.len());
+    let next = |state: usize, symbol: usize| if state == dead { dead } else { table[state][symbol].unwrap_or(dead) };
+
+    // [symbol][state] -> states leading to it
+    let mut inverse = vec![vec![Vec::new(); dead + 1]; symbols];
+    for state in 0..=dead {
+        for (symbol, inverse) in inverse.iter_mut().enumerate() {
+            inverse[next(state, symbol)].push(state);
+        }
+    }
+
+    // initially, states accepting same rule; dead state accepts nothing
+    let mut groups: HashMap<Option<usize>, Vec<usize>> = HashMap::new();
+    for state in 0..=dead {
+        groups.entry(accept.get(state).copied().flatten()).or_default().push(state);
+    }
+    let mut blocks = groups.into_values().collect::<Vec<_>>();
+    blocks.sort();
+
+    let mut block = vec![0; dead + 1];
+    for (i, states) in blocks.iter().enumerate() {
+        for &state in states {
+            block[state] = i;
+        }
+    }
+
+    let mut work = (0..blocks.len()).collect::<BTreeSet<_>>();
+    while let Some(splitter) = work.pop_first() {
+        for inverse in &inverse {
+            let predecessors = blocks[splitter]
+                .iter()
+                .flat_map(|&state| &inverse[state])
+                .copied()
+                .collect::<BTreeSet<_>>();
+
+            let mut touched: HashMap<usize, Vec<usize>> = HashMap::new();
+            for &state in &predecessors {
+                touched.entry(block[state]).or_default().push(state);
+            }
+
+            let mut touched = touched.into_iter().collect::<Vec<_>>();
+            touched.sort();
+            for (b, inside) in touched {
+                if inside.len() == blocks[b].len() {
+                    continue;
+                }
+
+                let outside = blocks[b].iter().copied().filter(|state| !predecessors.contains(state)).collect::<Vec<_>>();
+                let new = blocks.len();
+                for &state in &inside {
+                    block[state] = new;
+                }
+                blocks[b] = outside;
+                blocks.push(inside);
+
+                if work.contains(&b) || blocks[new].len() <= blocks[b].len() {
+                    work.insert(new);
+                } else {
+                    work.insert(b);
+                }
+            }
+        }
+    }
+
+    // renumber in order of first reach from start, leaving out dead state
+    let mut order = HashMap::from([(block[0], 0)]);
+    let mut queue = vec![block[0]];
+    let mut i = 0;
+    while i < queue.len() {
+        let representative = blocks[queue[i]][0];
+        for symbol in 0..symbols {
+            let target = block[next(representative, symbol)];
+            if target != block[dead] && !order.contains_key(&target) {
+                order.insert(target, queue.len());
+                queue.push(target);
+            }
+        }
+        i += 1;
+    }
+
+    let minimized = queue
+        .iter()
+        .map(|&b| {
+            let representative = blocks[b][0];
+            (0..symbols)
+                .map(|symbol| order.get(&block[next(representative, symbol)]).copied())
+                .collect()
+        })
+        .collect();
+    let accept = queue.iter().map(|&b| accept.get(blocks[b][0]).copied().flatten()).collect();
+
+    (minimized, accept)
+}
+
+// surrogates are no chars; interval is trimmed to either side of them
+fn chars(start: u32, end: u32) -> Option<RangeInclusive<char>> {
+    let start = char::from_u32(start).or_else(|| char::from_u32(0xE000))?;
+    let end = char::from_u32(end.min(MAX)).or_else(|| char::from_u32(0xD7FF))?;
+    (start <= end).then_some(start..=end)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::store::Store;
+
+    #[test]
+    fn test() {
+        let mut store = Store::new();
+        let lexer = Lexer::parse(
+            r#"
+If     : 'if' ;
+Ident  : [a-z]+ ;
+Hex    : '0x' [0-9a-f]+ ;
+Number : [0-9]+ ('.' [0-9]+)? ;
+Bang   : [a-z]* '!' ;
+Any    : [^a-z0-9 ] ;
+WS     : (' ' | '\t')+ ;
+            "#,
+            &mut store,
+        )
+        .unwrap();
+
+        let dfa = Dfa::new(&Nfa::new(lexer.rules(), 0));
+
+        assert_eq!(dfa.scan("if"), Some((0, 2)));
+        assert_eq!(dfa.scan("iff"), Some((1, 3)));
+        assert_eq!(dfa.scan("0x1f+"), Some((2, 4)));
+        assert_eq!(dfa.scan("0xg"), Some((3, 1)));
+        assert_eq!(dfa.scan("12.5."), Some((3, 4)));
+        assert_eq!(dfa.scan("12."), Some((3, 2)));
+        // longer wins over earlier, earlier on same length
+        assert_eq!(dfa.scan("ab!"), Some((4, 3)));
+        assert_eq!(dfa.scan("ab?"), Some((1, 2)));
+        assert_eq!(dfa.scan("!"), Some((4, 1)));
+        assert_eq!(dfa.scan("한글"), Some((5, 3)));
+        assert_eq!(dfa.scan(" \t x"), Some((6, 3)));
+        assert_eq!(dfa.scan(""), None);
+
+        // (a|b)*abb; dragon book 3.36, minimal DFA has 4 states
+        let mut store = Store::new();
+        let lexer = Lexer::parse("R : ('a' | 'b')* 'abb' ;", &mut store).unwrap();
+        let dfa = Dfa::new(&Nfa::new(lexer.rules(), 0));
+        assert_eq!(dfa.states(), 4);
+        assert_eq!(dfa.scan("babb"), Some((0, 4)));
+        assert_eq!(dfa.scan("abab"), None);
+    }
+}
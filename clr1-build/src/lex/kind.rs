@@ -0,0 +1,242 @@
+use crate::lex::lex::Lexer;
+use crate::pattern::Pattern;
+use crate::store::Store;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+// `Self` and such cannot be raw identifiers
+const RESERVED: &[&str] = &["Self", "self", "super", "crate", "_"];
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for",
+    "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become", "box", "do",
+    "final", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+// variants of generated `TokenKind`, in order of discriminant:
+// named rules as in lexer file, then implicit tokens as they appear in parser file, then `Error` of unmatched input
+pub struct Kinds {
+    // [token kind, variant, name in grammar]
+    list: Vec<(usize, String, String)>,
+}
+
+impl Kinds {
+    pub fn new(lexer: &Lexer, store: &Store<Cow<str>>) -> Self {
+        // implicit tokens are bare literals, inserted to front one by one
+        let (mut implicit, named): (Vec<_>, Vec<_>) =
+            lexer.rules().iter().partition(|rule| matches!(rule.pat(), Pattern::Literal(_)));
+        implicit.reverse();
+
+        // rule named `Error` gets `Error2`
+        let mut used = HashSet::from(["Error".to_string()]);
+        let mut list: Vec<_> = named
+            .into_iter()
+            .chain(implicit)
+            .map(|rule| {
+                let name = store[rule.id()].to_string();
+                let base = match rule.pat() {
+                    Pattern::Literal(literal) => literal_variant(literal),
+                    _ => ident(&name),
+                };
+
+                let mut variant = base.clone();
+                let mut i = 1;
+                while !used.insert(variant.clone()) {
+                    i += 1;
+                    variant = format!("{}{}", base, i);
+                }
+
+                (rule.id(), variant, name)
+            })
+            .collect();
+        list.push((lexer.error(), "Error".to_string(), store[lexer.error()].to_string()));
+
+        Self { list }
+    }
+
+    // discriminant of variant of token kind
+    pub fn index(&self, kind: usize) -> Option<usize> {
+        self.list.iter().position(|(k, _, _)| *k == kind)
+    }
+
+    // variant by token kind
+    pub fn variants(&self) -> HashMap<usize, String> {
+        self.list.iter().map(|(kind, variant, _)| (*kind, variant.clone())).collect()
+    }
+}
+
+// rule name as identifier
+pub fn ident(name: &str) -> String {
+    let mut variant = name.chars().map(|ch| if ch.is_alphanumeric() { ch } else { '_' }).collect::<String>();
+    if variant.starts_with(|ch: char| ch.is_ascii_digit()) {
+        variant.insert(0, '_');
+    }
+
+    if RESERVED.contains(&variant.as_str()) {
+        variant.push('_');
+    } else if KEYWORDS.contains(&variant.as_str()) {
+        variant.insert_str(0, "r#");
+    }
+
+    variant
+}
+
+// `'if'` as `If`, `'=='` as `EqEq`, `'${'` as `DollarLBrace`
+pub fn literal_variant(literal: &str) -> String {
+    let mut variant = String::new();
+    let mut upper = true;
+    for ch in literal.chars() {
+        if ch.is_alphanumeric() {
+            if upper {
+                variant.extend(ch.to_uppercase());
+            } else {
+                variant.push(ch);
+            }
+            upper = false;
+            continue;
+        }
+
+        upper = true;
+        match punctuation(ch) {
+            Some(name) => variant.push_str(name),
+            None => variant.push_str(&format!("U{:X}", ch as u32)),
+        }
+    }
+
+    if variant.is_empty() || variant.starts_with(|ch: char| ch.is_ascii_digit()) {
+        variant.insert(0, 'T');
+    }
+    variant
+}
+
+fn punctuation(ch: char) -> Option<&'static str> {
+    Some(match ch {
+        '+' => "Plus",
+        '-' => "Minus",
+        '*' => "Star",
+        '/' => "Slash",
+        '%' => "Percent",
+        '=' => "Eq",
+        '<' => "Lt",
+        '>' => "Gt",
+        '!' => "Bang",
+        '?' => "Question",
+        '&' => "Amp",
+        '|' => "Pipe",
+        '^' => "Caret",
+        '~' => "Tilde",
+        '.' => "Dot",
+        ',' => "Comma",
+        ':' => "Colon",
+        ';' => "Semi",
+        '(' => "LParen",
+        ')' => "RParen",
+        '[' => "LBracket",
+        ']' => "RBracket",
+        '{' => "LBrace",
+        '}' => "RBrace",
+        '#' => "Hash",
+        '@' => "At",
+        '$' => "Dollar",
+        '\'' => "Quote",
+        '"' => "DoubleQuote",
+        '`' => "Backtick",
+        '\\' => "Backslash",
+        '_' => "Underscore",
+        ' ' => "Space",
+        '\t' => "Tab",
+        '\n' => "Newline",
+        '\r' => "Return",
+        _ => return None,
+    })
+}
+
+// enum with `name()`, `Display`, `TryFrom<usize>` and `Into<usize>`, at indent of `generated` module
+impl Display for Kinds {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "    #[repr(u16)]")?;
+        writeln!(f, "    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]")?;
+        writeln!(f, "    pub enum TokenKind {{")?;
+        for (_, variant, _) in &self.list {
+            writeln!(f, "        {},", variant)?;
+        }
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    impl TokenKind {{")?;
+        writeln!(f, "        pub const ALL: &'static [TokenKind] = &[")?;
+        for (_, variant, _) in &self.list {
+            writeln!(f, "            TokenKind::{},", variant)?;
+        }
+        writeln!(f, "        ];")?;
+        writeln!(f)?;
+        writeln!(f, "        // name in lexer or parser file")?;
+        writeln!(f, "        pub fn name(&self) -> &'static str {{")?;
+        writeln!(f, "            match *self {{")?;
+        for (_, variant, name) in &self.list {
+            writeln!(f, "                TokenKind::{} => {:?},", variant, name)?;
+        }
+        writeln!(f, "            }}")?;
+        writeln!(f, "        }}")?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    impl std::fmt::Display for TokenKind {{")?;
+        writeln!(f, "        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{")?;
+        writeln!(f, "            f.write_str(self.name())")?;
+        writeln!(f, "        }}")?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    impl TryFrom<usize> for TokenKind {{")?;
+        writeln!(f, "        type Error = usize;")?;
+        writeln!(f)?;
+        writeln!(f, "        fn try_from(value: usize) -> Result<Self, usize> {{")?;
+        writeln!(f, "            TokenKind::ALL.get(value).copied().ok_or(value)")?;
+        writeln!(f, "        }}")?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    impl From<TokenKind> for usize {{")?;
+        writeln!(f, "        fn from(kind: TokenKind) -> usize {{")?;
+        writeln!(f, "            kind as usize")?;
+        writeln!(f, "        }}")?;
+        write!(f, "    }}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::parse::Grammar;
+    use crate::pattern::rules;
+
+    #[test]
+    fn test() {
+        let mut store = Store::new();
+        let mut lexer =
+            Lexer::parse("Ident : [a-z]+ ; 1st : '1' ; match : 'm' ; Self : 's' ; Error : '?' ;", &mut store).unwrap();
+        let rules = rules("S : Ident '==' '(' 'if' ')' ';' '$' 'if' '==' '==' '\\n' ;", &mut store).unwrap();
+        Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+
+        let kinds = Kinds::new(&lexer, &store);
+        let variants = kinds.list.iter().map(|(_, variant, _)| variant.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            variants,
+            [
+                "Ident", "_1st", "r#match", "Self_", "Error2", "EqEq", "LParen", "If", "RParen", "Semi", "Dollar",
+                "Newline", "Error"
+            ]
+        );
+        assert_eq!(kinds.list[5].2, "'=='");
+        assert_eq!(kinds.variants()[&store.add("Ident")], "Ident");
+
+        let code = kinds.to_string();
+        assert!(code.contains("TokenKind::EqEq => \"'=='\","));
+        assert!(code.contains("TokenKind::Newline => \"'\\\\n'\","));
+        assert!(code.contains("TokenKind::Error => \"$error\","));
+    }
+}
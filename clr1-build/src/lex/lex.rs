@@ -1,71 +1,250 @@
 use crate::error::{Error, ErrorKind};
-use crate::iter::{read_while, skip_while, Chars, Iter, Offset};
-use crate::pattern::{is_whitespace, parse, Pattern};
+use crate::lex::dfa::Dfa;
+use crate::lex::nfa::Nfa;
+use crate::pattern::{rules, Kind, Mode, Pattern, Quantifier};
 use crate::store::Store;
+use crate::util::TokenKind;
 use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+// where tokens of rule go; mirrors `clr1::lex::Channel`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum Channel {
+    Token,
+    Skip,
+    Hidden,
+}
 
 #[derive(Serialize)]
 pub struct Rule {
-    id: usize,
+    id: TokenKind,
+    channel: Channel,
+    // change of mode after token of this rule
+    mode: Option<Mode>,
+    // compiled into DFA of its mode, which runtime lexes by
+    #[serde(skip)]
     pat: Pattern,
+    // index of mode this rule is in
+    #[serde(skip)]
+    within: usize,
 }
 
 impl Rule {
-    fn new(id: usize, pat: Pattern) -> Self {
-        Self { id, pat }
+    fn new(id: usize, channel: Channel, pat: Pattern) -> Self {
+        Self {
+            id: TokenKind(id),
+            channel,
+            mode: None,
+            pat,
+            within: 0,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id.0
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    pub fn pat(&self) -> &Pattern {
+        &self.pat
+    }
+
+    pub fn within(&self) -> usize {
+        self.within
     }
 }
 
+pub struct Lexer {
+    rules: Vec<Rule>,
+    // names of modes; rules before any `%mode` are in first one, `Default`
+    modes: Vec<usize>,
+    // names of fragment rules, which were inlined and emit no token
+    fragments: HashSet<usize>,
+    // token kind of unmatched input, named `$error`
+    error: usize,
+}
+
+// rules with DFA of each mode; mirrors `clr1::lex::Lexer`
 #[derive(Serialize)]
-pub struct Lexer(Vec<Rule>);
+#[serde(rename = "Lexer")]
+pub struct Compiled<'l> {
+    rules: &'l [Rule],
+    modes: Vec<Dfa>,
+    error: TokenKind,
+}
 
 impl Lexer {
-    pub fn parse<'a>(from: &'a str, store: &mut Store<&'a str>) -> Result<Self, Error> {
-        let mut from: Iter<'a> = from.into();
+    pub fn parse<'a>(from: &'a str, store: &mut Store<Cow<'a, str>>) -> Result<Self, Error> {
+        let rules = rules(from, store)?;
+        let precedences = rules.precedences.iter().map(|precedence| precedence.at);
+        if let Some(at) = precedences.chain(rules.types.iter().map(|ty| ty.at)).next() {
+            return Err(ErrorKind::UnexpectedDirective.at(at));
+        }
 
-        let mut rules = Vec::new();
-        loop {
-            skip_while(&mut from, is_whitespace);
+        let mut modes = vec![store.add("Default")];
+        for section in &rules.sections {
+            if !modes.contains(&section.mode) {
+                modes.push(section.mode);
+            }
+        }
 
-            match from.peek() {
-                Some((_, '#')) => {
-                    from.next().unwrap();
-                    skip_while(&mut from, |ch: char| ch != '\n');
-                    continue;
-                }
-                None => break Ok(Self(rules)),
-                _ => {}
+        // patterns without mode change at their end
+        let mut patterns = Vec::new();
+        for def in &rules.definitions {
+            let (pat, mode) = split(&def.pat);
+            if has_directive(&pat) || (def.kind == Kind::Fragment && mode.is_some()) {
+                return Err(ErrorKind::UnexpectedDirective.at(def.at));
             }
+            patterns.push((pat, mode));
+        }
 
-            let Some((begin, end, name)) =
-                read_while(&mut from, |ch: char| !is_whitespace(ch) && ch != ':')
-            else {
-                // starts with ':'
-                let (offset, _) = from.peek().unwrap();
-                break Err(ErrorKind::EmptyName.at(offset));
-            };
-            if name.contains([':', '@']) {
-                break Err(ErrorKind::InvalidRuleHead.at(begin));
+        let mut defined = HashMap::new();
+        for (def, (pat, _)) in rules.definitions.iter().zip(&patterns) {
+            if defined.insert(def.name, pat).is_some() {
+                return Err(ErrorKind::DuplicateRule(store[def.name].to_string()).at(def.at));
             }
-            let name = store.add(name);
-
-            skip_while(&mut from, is_whitespace);
-
-            // consume ':'
-            match from.next() {
-                Some((_, ':')) => {}
-                Some((offset, _)) => break Err(ErrorKind::Expected(':').at(offset)),
-                None => {
-                    break Err(
-                        ErrorKind::Expected(':').at(Offset::new(end.line(), end.column() + 1))
-                    );
+        }
+
+        let index = |name: usize| {
+            let index = modes.iter().position(|&mode| mode == name);
+            index.ok_or_else(|| ErrorKind::UndefinedMode(store[name].to_string()))
+        };
+
+        let mut list = Vec::new();
+        let mut fragments = HashSet::new();
+        for (def, (pat, mode)) in rules.definitions.iter().zip(&patterns) {
+            let channel = match def.kind {
+                Kind::Token => Channel::Token,
+                Kind::Skip => Channel::Skip,
+                Kind::Hidden => Channel::Hidden,
+                Kind::Fragment => {
+                    fragments.insert(def.name);
+                    continue;
                 }
+            };
+
+            let mode = match *mode {
+                Some(Mode::Push(name)) => Some(Mode::Push(index(name).map_err(|kind| kind.at(def.at))?)),
+                Some(Mode::Switch(name)) => Some(Mode::Switch(index(name).map_err(|kind| kind.at(def.at))?)),
+                mode => mode,
+            };
+
+            let pat = inline(pat, &defined, &mut vec![def.name], store).map_err(|kind| kind.at(def.at))?;
+            list.push(Rule {
+                mode,
+                within: def.mode.map_or(0, |name| index(name).unwrap()),
+                ..Rule::new(def.name, channel, pat)
+            });
+        }
+
+        Ok(Self {
+            rules: list,
+            modes,
+            fragments,
+            error: store.add("$error"),
+        })
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn error(&self) -> usize {
+        self.error
+    }
+
+    pub fn is_fragment(&self, id: usize) -> bool {
+        self.fragments.contains(&id)
+    }
+
+    pub fn channel(&self, id: usize) -> Option<Channel> {
+        self.rules.iter().find(|rule| rule.id() == id).map(|rule| rule.channel)
+    }
+
+    pub fn compile(&self) -> Compiled<'_> {
+        Compiled {
+            rules: &self.rules,
+            modes: (0..self.modes.len()).map(|mode| Dfa::new(&Nfa::new(&self.rules, mode))).collect(),
+            error: TokenKind(self.error),
+        }
+    }
+
+    // id of token rule which consists of the literal only
+    pub fn literal(&self, literal: &str) -> Option<usize> {
+        let mut tokens = self.rules.iter().filter(|rule| rule.channel == Channel::Token);
+        tokens.find_map(|rule| match &rule.pat {
+            Pattern::Group(list) => match list.as_slice() {
+                [Pattern::Literal(str)] if str == literal => Some(rule.id()),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    // implicit tokens precede named rules, so `'if'` wins over `[a-z]+` of same length
+    pub fn implicit(&mut self, id: usize, literal: String) {
+        self.rules.insert(0, Rule::new(id, Channel::Token, Pattern::Literal(literal)));
+    }
+}
+
+// copy of pattern with references replaced by patterns they refer to. `path` is chain of rules being inlined.
+fn inline(
+    pat: &Pattern,
+    defined: &HashMap<usize, &Pattern>,
+    path: &mut Vec<usize>,
+    store: &Store<Cow<str>>,
+) -> Result<Pattern, ErrorKind> {
+    let list = |list: &[Pattern], path: &mut Vec<usize>| {
+        list.iter().map(|pat| inline(pat, defined, path, store)).collect::<Result<Vec<_>, _>>()
+    };
+
+    Ok(match pat {
+        // capture name means nothing to lexer
+        Pattern::Reference(id, _) => {
+            let referred = defined.get(id).ok_or_else(|| ErrorKind::UndefinedRule(store[*id].to_string()))?;
+            if let Some(i) = path.iter().position(|name| name == id) {
+                let cycle = path[i..].iter().chain([id]).map(|&name| store[name].as_ref()).collect::<Vec<_>>();
+                return Err(ErrorKind::RecursiveRule(cycle.join(" -> ")));
             }
 
-            let pattern = parse(&mut from, store)?;
+            path.push(*id);
+            let pat = inline(referred, defined, path, store)?;
+            path.pop();
 
-            rules.push(Rule::new(name, pattern));
+            pat
+        }
+        Pattern::Quantifier(quantifier) => {
+            let what = inline(quantifier.what(), defined, path, store)?;
+            Quantifier::new(what, quantifier.range().clone(), quantifier.at()).into()
         }
+        Pattern::Group(group) => Pattern::Group(list(group, path)?),
+        Pattern::Or(alternatives, at) => Pattern::Or(list(alternatives, path)?, *at),
+        Pattern::Literal(_) | Pattern::Class(_) | Pattern::Prec(_) | Pattern::Mode(_) | Pattern::Code(_) => pat.clone(),
+    })
+}
+
+// rule pattern without `%push`, `%pop` or `%switch` at its end, and that mode change
+fn split(pat: &Pattern) -> (Pattern, Option<Mode>) {
+    match pat {
+        Pattern::Group(list) => match list.as_slice() {
+            [rest @ .., Pattern::Mode(mode)] => (Pattern::Group(rest.to_vec()), Some(*mode)),
+            _ => (pat.clone(), None),
+        },
+        _ => (pat.clone(), None),
+    }
+}
+
+// `%prec` and code are meaningful to parser only, and mode change only at end of rule
+fn has_directive(pat: &Pattern) -> bool {
+    match pat {
+        Pattern::Prec(_) | Pattern::Mode(_) | Pattern::Code(_) => true,
+        Pattern::Quantifier(quantifier) => has_directive(quantifier.what()),
+        Pattern::Group(list) | Pattern::Or(list, _) => list.iter().any(has_directive),
+        Pattern::Reference(..) | Pattern::Literal(_) | Pattern::Class(_) => false,
     }
 }
 
@@ -102,7 +281,93 @@ String : '"' ( [^"\\\n] | ('\\' .) )* '"' ;
             &mut store,
         )
         .unwrap();
-        
-        println!("{}", ser::to_string(&lexer, Default::default()).unwrap());
+
+        // rules are generated without patterns, which are lexed by DFA
+        let generated = ser::to_string(&lexer.compile(), Default::default()).unwrap();
+        let rule = "Rule {\n            id: 0,\n            channel: Channel::Token,\n            mode: None\n";
+        assert!(generated.starts_with(&format!("Lexer {{\n    rules: &[\n        {}", rule)));
+        assert!(generated.contains("\n    ],\n    modes: &[\n        Dfa {\n            offsets: &["));
+
+        // references are inlined
+        let dfa = lexer.compile().modes.remove(0);
+        assert_eq!(dfa.scan("0x1F"), Some((3, 4)));
+        assert_eq!(dfa.scan("12"), Some((3, 2)));
+        assert_eq!(dfa.scan("x = 0x10"), Some((4, 8)));
+
+        let error = |from| Lexer::parse(from, &mut Store::new()).err().unwrap().to_string();
+        assert!(error("A : 'a' B ; B : 'b' C? ; C : A ;").ends_with("lexer rule refers to itself: A -> B -> C -> A"));
+        assert!(error("A : 'a' A? ;").ends_with("lexer rule refers to itself: A -> A"));
+        assert!(error("A : B ;").ends_with("rule 'B' is not defined"));
+        assert!(error("A : 'a' ; A : 'b' ;").ends_with("rule 'A' is already defined"));
+        assert!(error("fragment").ends_with("unexpected end of file"));
+
+        // fragment emits no token, and rule may still be named `fragment`
+        let mut store = Store::new();
+        let lexer = Lexer::parse("fragment Digit : [0-9] ; Number : Digit+ ; fragment : 'f' ;", &mut store).unwrap();
+        assert_eq!(lexer.rules().len(), 2);
+        assert!(lexer.is_fragment(store.add("Digit")));
+        assert!(!lexer.is_fragment(store.add("fragment")));
+
+        let dfa = lexer.compile().modes.remove(0);
+        assert_eq!(dfa.scan("12"), Some((0, 2)));
+        assert_eq!(dfa.scan("f"), Some((1, 1)));
+
+        // string interpolation
+        let mut store = Store::new();
+        let lexer = Lexer::parse(
+            r#"
+Ident : [a-z]+ ;
+Close : '}' %pop ;
+Quote : '"' %push String ;
+
+%mode String ;
+Text  : [^"$]+ ;
+Open  : '${' %push Default ;
+End   : '"' %switch Default ;
+            "#,
+            &mut store,
+        )
+        .unwrap();
+
+        let modes = lexer.rules().iter().map(|rule| (rule.within, rule.mode)).collect::<Vec<_>>();
+        assert_eq!(
+            modes,
+            [
+                (0, None),
+                (0, Some(Mode::Pop)),
+                (0, Some(Mode::Push(1))),
+                (1, None),
+                (1, Some(Mode::Push(0))),
+                (1, Some(Mode::Switch(0)))
+            ]
+        );
+
+        let mut modes = lexer.compile().modes;
+        assert_eq!(modes.len(), 2);
+        let string = modes.pop().unwrap();
+        assert_eq!(string.scan("ab ${x}"), Some((3, 3)));
+        assert_eq!(string.scan("${x}"), Some((4, 2)));
+        assert_eq!(string.scan("}"), Some((3, 1)));
+        assert_eq!(modes[0].scan("ab ${x}"), Some((0, 2)));
+        assert_eq!(modes[0].scan("\""), Some((2, 1)));
+
+        assert!(error("A : 'a' %push B ;").ends_with("mode 'B' is not defined"));
+        assert!(error("A : 'a' %pop 'b' ;").ends_with("directive is not allowed here"));
+        assert!(error("A : 'a' %pop | 'b' ;").ends_with("directive is not allowed here"));
+        assert!(error("fragment A : 'a' %pop ;").ends_with("directive is not allowed here"));
+    }
+
+    #[test]
+    fn precedence() {
+        let lexer = |from| {
+            let lexer = Lexer::parse(from, &mut Store::new()).unwrap();
+            ser::to_string(&lexer.compile(), Default::default()).unwrap()
+        };
+
+        // `|` binds looser than sequence, so `'0x' Hex+ | Digit+` is not `'0x' (Hex+ | Digit+)` as it used to be
+        assert_eq!(lexer("A : 'a' 'b' | 'c' ;"), lexer("A : ('a' 'b') | 'c' ;"));
+        assert_ne!(lexer("A : 'a' 'b' | 'c' ;"), lexer("A : 'a' ('b' | 'c') ;"));
+        assert_eq!(lexer("A : 'a' | 'b' 'c' | 'd' ;"), lexer("A : 'a' | ('b' 'c') | 'd' ;"));
+        assert_eq!(lexer("A : ('a' 'b' | 'c')* ;"), lexer("A : (('a' 'b') | 'c')* ;"));
     }
 }
@@ -1,3 +1,8 @@
+#[allow(clippy::module_inception)]
 mod lex;
+mod nfa;
+mod dfa;
+mod kind;
 
-pub use lex::Lexer;
+pub use kind::{ident, literal_variant, Kinds};
+pub use lex::{Channel, Lexer};
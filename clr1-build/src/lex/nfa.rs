@@ -0,0 +1,169 @@
+use crate::lex::lex::Rule;
+use crate::pattern::{ClassItem, Pattern};
+
+// inclusive range of code points
+pub type Range = (u32, u32);
+
+pub const MAX: u32 = char::MAX as u32;
+
+#[derive(Default)]
+pub struct State {
+    epsilon: Vec<usize>,
+    edges: Vec<(Vec<Range>, usize)>,
+    // index of rule which matched on reaching this state
+    accept: Option<usize>,
+}
+
+impl State {
+    pub fn epsilon(&self) -> &[usize] {
+        &self.epsilon
+    }
+
+    pub fn edges(&self) -> &[(Vec<Range>, usize)] {
+        &self.edges
+    }
+
+    pub fn accept(&self) -> Option<usize> {
+        self.accept
+    }
+}
+
+// Thompson construction of rules; state 0 branches to start of each rule
+pub struct Nfa {
+    states: Vec<State>,
+}
+
+impl Nfa {
+    // rules of given mode only; accepting states still tell index among all rules
+    pub fn new(rules: &[Rule], mode: usize) -> Self {
+        let mut nfa = Self { states: Vec::new() };
+
+        let start = nfa.state();
+        for (i, rule) in rules.iter().enumerate().filter(|(_, rule)| rule.within() == mode) {
+            let (begin, end) = nfa.pattern(rule.pat());
+            nfa.states[start].epsilon.push(begin);
+            nfa.states[end].accept = Some(i);
+        }
+
+        nfa
+    }
+
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+
+    fn state(&mut self) -> usize {
+        self.states.push(State::default());
+        self.states.len() - 1
+    }
+
+    fn edge(&mut self, ranges: Vec<Range>) -> (usize, usize) {
+        let (begin, end) = (self.state(), self.state());
+        self.states[begin].edges.push((ranges, end));
+        (begin, end)
+    }
+
+    // fragment with single entry and exit
+    fn pattern(&mut self, pat: &Pattern) -> (usize, usize) {
+        match pat {
+            Pattern::Literal(str) => {
+                let chars = str.chars().map(|ch| vec![(ch as u32, ch as u32)]).collect::<Vec<_>>();
+                self.sequence(chars.into_iter().map(Fragment::Ranges))
+            }
+            Pattern::Class(class) => {
+                let mut ranges = class
+                    .list()
+                    .iter()
+                    .map(|item| match item {
+                        ClassItem::Char(ch) => (*ch as u32, *ch as u32),
+                        ClassItem::Range(range) => (*range.start() as u32, *range.end() as u32),
+                    })
+                    .collect::<Vec<_>>();
+
+                if class.deny() {
+                    ranges = complement(ranges);
+                }
+                self.edge(ranges)
+            }
+            Pattern::Quantifier(quantifier) => {
+                let (min, max) = (*quantifier.range().start(), *quantifier.range().end());
+                let what = quantifier.what();
+
+                let mut fragments = (0..min).map(|_| Fragment::Pattern(what)).collect::<Vec<_>>();
+                if max == usize::MAX {
+                    fragments.push(Fragment::Star(what));
+                } else {
+                    fragments.extend((min..max).map(|_| Fragment::Optional(what)));
+                }
+
+                self.sequence(fragments)
+            }
+            Pattern::Group(list) => self.sequence(list.iter().map(Fragment::Pattern)),
+            Pattern::Or(list, _) => {
+                let (begin, end) = (self.state(), self.state());
+                for pat in list {
+                    let (b, e) = self.pattern(pat);
+                    self.states[begin].epsilon.push(b);
+                    self.states[e].epsilon.push(end);
+                }
+                (begin, end)
+            }
+            Pattern::Reference(..) => unreachable!("inlined by lexer parser"),
+            Pattern::Prec(_) | Pattern::Mode(_) | Pattern::Code(_) => unreachable!("rejected by lexer parser"),
+        }
+    }
+
+    fn sequence<'p>(&mut self, fragments: impl IntoIterator<Item = Fragment<'p>>) -> (usize, usize) {
+        let begin = self.state();
+        let mut end = begin;
+
+        for fragment in fragments {
+            let (b, e) = match fragment {
+                Fragment::Ranges(ranges) => self.edge(ranges),
+                Fragment::Pattern(pat) => self.pattern(pat),
+                Fragment::Optional(pat) => {
+                    let (b, e) = self.pattern(pat);
+                    self.states[b].epsilon.push(e);
+                    (b, e)
+                }
+                Fragment::Star(pat) => {
+                    let (b, e) = self.pattern(pat);
+                    let (begin, end) = (self.state(), self.state());
+                    self.states[begin].epsilon.extend([b, end]);
+                    self.states[e].epsilon.extend([b, end]);
+                    (begin, end)
+                }
+            };
+
+            self.states[end].epsilon.push(b);
+            end = e;
+        }
+
+        (begin, end)
+    }
+}
+
+enum Fragment<'p> {
+    Ranges(Vec<Range>),
+    Pattern(&'p Pattern),
+    Optional(&'p Pattern),
+    Star(&'p Pattern),
+}
+
+fn complement(mut ranges: Vec<Range>) -> Vec<Range> {
+    ranges.sort();
+
+    let mut out = Vec::new();
+    let mut next = 0;
+    for (start, end) in ranges {
+        if start > next {
+            out.push((next, start - 1));
+        }
+        next = next.max(end.saturating_add(1));
+    }
+    if next <= MAX {
+        out.push((next, MAX));
+    }
+
+    out
+}
@@ -1,8 +1,12 @@
 use crate::error::{Error, ErrorKind};
 use crate::iter::Offset;
-use crate::lex::Lexer;
+use crate::lex::{Kinds, Lexer};
+use crate::parse::{added_conflicts, lalr, minimal, Analysis, Ast, Automaton, Grammar, Inline, Parser};
+use crate::pattern::{rules, Rules};
 use crate::ser::Options;
 use crate::store::Store;
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
 use std::fs::read_to_string;
 use std::path::Path;
 
@@ -16,25 +20,213 @@ mod pattern;
 mod ser;
 mod util;
 
-pub fn build(lexer: impl AsRef<Path>, parser: impl AsRef<Path>) -> Result<String, Error> {
+fn load<T>(
+    lexer: impl AsRef<Path>,
+    parser: impl AsRef<Path>,
+    f: impl FnOnce(&Lexer, &Grammar, &Rules, &Store<Cow<str>>) -> Result<T, Error>,
+) -> Result<T, Error> {
     let mut store = Store::new();
 
     let lex = read_to_string(lexer).map_err(|e| ErrorKind::Io(e).at(Offset::new(0, 0)))?;
-    let lexer = Lexer::parse(&lex, &mut store)?;
+    let parse = read_to_string(parser).map_err(|e| ErrorKind::Io(e).at(Offset::new(0, 0)))?;
+
+    let mut lexer = Lexer::parse(&lex, &mut store)?;
+
+    let rules = rules(&parse, &mut store)?;
+    let grammar = Grammar::new(&rules, &mut lexer, &mut store)?;
+
+    f(&lexer, &grammar, &rules, &store)
+}
+
+// human-readable dump of grammar, nullable/FIRST/FOLLOW sets and LR(1) states for debugging
+pub fn describe(lexer: impl AsRef<Path>, parser: impl AsRef<Path>) -> Result<String, Error> {
+    load(lexer, parser, |_, grammar, _, _| {
+        let analysis = Analysis::new(grammar);
+        let automaton = Automaton::new(&analysis);
+
+        let (parser, conflicts) = Parser::new(&automaton);
+        let conflicts = conflicts
+            .iter()
+            .map(|conflict| match conflict.report(&automaton) {
+                Some(report) => format!("{}\n", report),
+                None => format!("{}\n", conflict.error(&analysis, &automaton)),
+            })
+            .collect::<String>();
+
+        let report = Report::new(&automaton, Some(&lalr(&automaton)), Some(&minimal(&automaton)), &parser);
+
+        Ok(format!("{}\n{}\n{}\n{}{}\n", grammar, analysis, automaton, conflicts, report))
+    })
+}
+
+#[derive(Default, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+    // fail build on first conflict
+    #[default]
+    Error,
+    // resolve conflict (shift wins, earlier production wins) and report it
+    Warning,
+}
+
+#[derive(Default, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    // one state per distinct LR(1) item set; most precise, largest
+    #[default]
+    Canonical,
+    // states of same LR(0) core merged; smallest, may add reduce/reduce conflicts
+    Lalr,
+    // Pager's minimal LR(1): states of same core merged where it adds no conflict canonical LR(1) does not have;
+    // see `parse::minimal`
+    Minimal,
+}
+
+#[derive(Default)]
+pub struct Config {
+    pub conflicts: Severity,
+    pub mode: Mode,
+    // build states of every mode, not only of selected one, to compare them in `Output::report`
+    pub compare: bool,
+}
+
+// number of states each mode produces; `None` for mode which was neither selected nor compared
+pub struct Report {
+    pub canonical: usize,
+    pub lalr: Option<usize>,
+    pub minimal: Option<usize>,
+    // reduce/reduce conflicts LALR(1) merging adds, which neither canonical LR(1) nor minimal LR(1) have
+    pub lalr_conflicts: Option<usize>,
+    // bytes of ACTION and GOTO of selected mode, uncompressed and as generated
+    pub dense_bytes: usize,
+    pub compressed_bytes: usize,
+}
+
+impl Report {
+    fn new(automaton: &Automaton, lalr: Option<&[usize]>, minimal: Option<&[usize]>, parser: &Parser) -> Self {
+        let count = |blocks: &[usize]| blocks.iter().max().map_or(0, |&max| max + 1);
+        let (dense_bytes, compressed_bytes) = parser.size();
 
-    let lexer = ser::to_string(&lexer, Options { initial_indent: 2 })
-        .map_err(|e| ErrorKind::from(e).at(Offset::new(0, 0)))?;
+        Self {
+            canonical: automaton.states().len(),
+            lalr: lalr.map(count),
+            minimal: minimal.map(count),
+            lalr_conflicts: lalr.map(|lalr| added_conflicts(automaton, lalr)),
+            dense_bytes,
+            compressed_bytes,
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "states: {} canonical LR(1)", self.canonical)?;
+        if let Some(lalr) = self.lalr {
+            write!(f, ", {} LALR(1)", lalr)?;
+        }
+        if let Some(minimal) = self.minimal {
+            write!(f, ", {} minimal LR(1)", minimal)?;
+        }
+        if let Some(conflicts) = self.lalr_conflicts {
+            write!(f, "; LALR(1) adds {} reduce/reduce conflicts", conflicts)?;
+        }
+        write!(
+            f,
+            "\ntables: {} bytes compressed from {} bytes ({} saved)",
+            self.compressed_bytes,
+            self.dense_bytes,
+            self.dense_bytes.saturating_sub(self.compressed_bytes)
+        )
+    }
+}
+
+pub struct Output {
+    pub code: String,
+    pub warnings: Vec<Error>,
+    // conflicts settled by precedence
+    pub resolved: Vec<String>,
+    pub report: Report,
+}
+
+pub fn build(lexer: impl AsRef<Path>, parser: impl AsRef<Path>) -> Result<String, Error> {
+    generate(lexer, parser, Config::default()).map(|output| output.code)
+}
+
+pub fn generate(lexer: impl AsRef<Path>, parser: impl AsRef<Path>, config: Config) -> Result<Output, Error> {
+    load(lexer, parser, |lexer, grammar, rules, store| {
+        let analysis = Analysis::new(grammar);
+        let canonical = Automaton::new(&analysis);
+
+        // partitions of canonical states, built only if selected or compared
+        let lalr = (config.mode == Mode::Lalr || config.compare).then(|| lalr(&canonical));
+        let minimal = (config.mode == Mode::Minimal || config.compare).then(|| minimal(&canonical));
+        let blocks = match config.mode {
+            Mode::Canonical => None,
+            Mode::Lalr => lalr.as_deref(),
+            Mode::Minimal => minimal.as_deref(),
+        };
+        let merged = blocks.map(|blocks| canonical.merge(blocks));
+        let automaton = merged.as_ref().unwrap_or(&canonical);
 
-    let generated = format!(
-        r#"
+        let (mut parser, conflicts) = Parser::new(automaton);
+        let report = Report::new(&canonical, lalr.as_deref(), minimal.as_deref(), &parser);
+
+        let resolved = conflicts.iter().filter_map(|conflict| conflict.report(automaton)).collect();
+
+        let mut warnings = conflicts
+            .iter()
+            .filter(|conflict| conflict.resolution().is_none())
+            .map(|conflict| conflict.error(&analysis, automaton));
+        if config.conflicts == Severity::Error
+            && let Some(error) = warnings.next()
+        {
+            return Err(error);
+        }
+        let warnings = warnings.collect();
+
+        let kinds = Kinds::new(lexer, store);
+        parser.index_kinds(|kind| kinds.index(kind));
+        let ast = Ast::new(rules, grammar, store)?;
+        let inline = Inline::new(rules, grammar, &ast, store)?;
+        let options = || Options {
+            initial_indent: 2,
+            kinds: kinds.variants(),
+        };
+
+        let lexer = ser::to_string(&lexer.compile(), options()).map_err(|e| ErrorKind::from(e).at(Offset::new(0, 0)))?;
+        let parser = ser::to_string(&parser, options()).map_err(|e| ErrorKind::from(e).at(Offset::new(0, 0)))?;
+
+        let code = format!(
+            r#"
+// {}
+#[allow(dead_code, clippy::all)]
 mod generated {{
     use clr1::lex::*;
+    use clr1::parse::*;
+
+{}
+
+    pub static LEXER: Lexer<TokenKind> = {};
+
+    pub static PARSER: Parser<TokenKind> = {};
+
+{}
 
-    static LEXER: Lexer = {};
+{}{}
 }}
 "#,
-        lexer
-    );
+            report.to_string().replace('\n', "\n// "),
+            kinds,
+            lexer,
+            parser,
+            ast,
+            ast.actions(),
+            inline.map(|inline| format!("\n\n{}", inline)).unwrap_or_default()
+        );
 
-    Ok(generated)
+        Ok(Output {
+            code,
+            warnings,
+            resolved,
+            report,
+        })
+    })
 }
@@ -0,0 +1,166 @@
+use crate::parse::grammar::{Grammar, Symbol};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+// nullable, FIRST and FOLLOW sets of nonterminals
+pub struct Analysis<'g> {
+    grammar: &'g Grammar,
+    nullable: Vec<bool>,
+    first: Vec<BTreeSet<usize>>,
+    follow: Vec<BTreeSet<usize>>,
+}
+
+impl<'g> Analysis<'g> {
+    pub fn new(grammar: &'g Grammar) -> Self {
+        let count = grammar.nonterminals().len();
+        let mut analysis = Self {
+            grammar,
+            nullable: vec![false; count],
+            first: vec![BTreeSet::new(); count],
+            follow: vec![BTreeSet::new(); count],
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for production in grammar.productions() {
+                let head = production.head();
+
+                let mut set = BTreeSet::new();
+                let nullable = analysis.first_of(production.body(), &mut set);
+
+                if nullable && !analysis.nullable[head] {
+                    analysis.nullable[head] = true;
+                    changed = true;
+                }
+
+                for terminal in set {
+                    changed |= analysis.first[head].insert(terminal);
+                }
+            }
+        }
+
+        analysis.follow[Grammar::ACCEPT].insert(Grammar::END);
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for production in grammar.productions() {
+                let body = production.body();
+                for (i, symbol) in body.iter().enumerate() {
+                    let Symbol::Nonterminal(nonterminal) = *symbol else {
+                        continue;
+                    };
+
+                    let mut set = BTreeSet::new();
+                    if analysis.first_of(&body[i + 1..], &mut set) {
+                        set.extend(&analysis.follow[production.head()]);
+                    }
+
+                    for terminal in set {
+                        changed |= analysis.follow[nonterminal].insert(terminal);
+                    }
+                }
+            }
+        }
+
+        analysis
+    }
+
+    pub fn grammar(&self) -> &'g Grammar {
+        self.grammar
+    }
+
+    // collects FIRST of sequence; returns true when whole sequence is nullable
+    pub fn first_of(&self, seq: &[Symbol], into: &mut BTreeSet<usize>) -> bool {
+        for symbol in seq {
+            match *symbol {
+                Symbol::Terminal(terminal) => {
+                    into.insert(terminal);
+                    return false;
+                }
+                Symbol::Nonterminal(nonterminal) => {
+                    into.extend(&self.first[nonterminal]);
+                    if !self.nullable[nonterminal] {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl Display for Analysis<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let names = |f: &mut Formatter<'_>, set: &BTreeSet<usize>| {
+            for &terminal in set {
+                write!(f, " {}", self.grammar.name(Symbol::Terminal(terminal)))?;
+            }
+            writeln!(f)
+        };
+
+        for i in 0..self.grammar.nonterminals().len() {
+            writeln!(f, "{}", self.grammar.name(Symbol::Nonterminal(i)))?;
+            writeln!(f, "    nullable: {}", self.nullable[i])?;
+            write!(f, "    first   :")?;
+            names(f, &self.first[i])?;
+            write!(f, "    follow  :")?;
+            names(f, &self.follow[i])?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::pattern::rules;
+    use crate::store::Store;
+
+    #[test]
+    fn test() {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse("Id : [a-z]+ ;", &mut store).unwrap();
+
+        let rules = rules(
+            r#"
+E     : T ETail ;
+ETail : '+' T ETail | ;
+T     : F TTail ;
+TTail : '*' F TTail | ;
+F     : '(' E ')' | Id ;
+            "#,
+            &mut store,
+        )
+        .unwrap();
+
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        let analysis = Analysis::new(&grammar);
+
+        let names = |set: &BTreeSet<usize>| {
+            set.iter()
+                .map(|&t| grammar.name(Symbol::Terminal(t)))
+                .collect::<Vec<_>>()
+        };
+
+        // E, ETail, T, TTail, F
+        assert!(!analysis.nullable[1]);
+        assert!(analysis.nullable[2]);
+        assert!(analysis.nullable[4]);
+        assert_eq!(names(&analysis.first[1]), ["Id", "'('"]);
+        assert_eq!(names(&analysis.first[4]), ["'*'"]);
+        assert_eq!(names(&analysis.follow[1]), ["$", "')'"]);
+        assert_eq!(names(&analysis.follow[3]), ["$", "'+'", "')'"]);
+        assert_eq!(names(&analysis.follow[5]), ["$", "'+'", "'*'", "')'"]);
+
+        let analysis = analysis.to_string();
+        assert!(analysis.starts_with("$accept\n    nullable: false\n    first   : Id '('\n    follow  : $\n"));
+        assert!(analysis.contains("\nTTail\n    nullable: true\n    first   : '*'\n    follow  : $ '+' ')'\n"));
+    }
+}
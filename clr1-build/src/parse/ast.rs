@@ -0,0 +1,653 @@
+use crate::error::{Error, ErrorKind};
+use crate::lex::{ident, literal_variant};
+use crate::parse::grammar::Grammar;
+use crate::parse::lower::alternatives;
+use crate::pattern::{Pattern, Rules};
+use crate::store::Store;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+// what capture holds
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum Target {
+    // `&str` of token
+    Token,
+    // type of rule, by index of its definition
+    Rule(usize),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum Count {
+    One,
+    Optional,
+    Many,
+}
+
+pub(super) struct Field {
+    pub(super) name: String,
+    pub(super) capture: String,
+    pub(super) target: Target,
+    pub(super) count: Count,
+}
+
+enum Content {
+    // captures of alternative
+    Fields(Vec<Field>),
+    // alternative which is single reference without capture
+    Single(Target),
+    Unit,
+}
+
+struct Variant {
+    name: String,
+    production: usize,
+    content: Content,
+}
+
+enum Shape {
+    // its only production, and fields
+    Struct(usize, Vec<Field>),
+    // its only production, which is single reference without capture
+    Single(usize, Target),
+    // rule of top-level `|`, whose alternatives are productions
+    Enum(Vec<Variant>),
+}
+
+struct Type {
+    name: String,
+    // whether it has `&str`, directly or not
+    lifetime: bool,
+    shape: Shape,
+}
+
+// `ast` module of generated code: type per parser rule with field per `@name`, made from syntax tree node by `From`
+pub struct Ast {
+    types: Vec<Type>,
+}
+
+// [name, target, min, max] of each capture in order of appearance; max is `usize::MAX` if unbounded
+type Captures = Vec<(usize, Target, usize, usize)>;
+
+impl Ast {
+    pub fn new(rules: &Rules, grammar: &Grammar, store: &Store<Cow<str>>) -> Result<Self, Error> {
+        let indices = rules.definitions.iter().enumerate().map(|(i, def)| (def.name, i)).collect::<HashMap<_, _>>();
+
+        let mut types = Vec::new();
+        for (i, def) in rules.definitions.iter().enumerate() {
+            let fields = |pat: &Pattern| {
+                let captures = captures(pat, &indices, store).map_err(|kind| kind.at(def.at))?;
+                Ok::<_, Error>(captures.into_iter().map(|capture| field(capture, store)).collect::<Vec<_>>())
+            };
+
+            let alternatives = alternatives(&def.pat);
+            let productions = grammar.nonterminals()[Grammar::ACCEPT + 1 + i].productions();
+            let shape = if alternatives.len() == 1 {
+                let fields = fields(&def.pat)?;
+                match sequence(&def.pat).as_slice() {
+                    [Pattern::Reference(id, None)] if fields.is_empty() => {
+                        Shape::Single(productions[0], target(*id, &indices))
+                    }
+                    _ => Shape::Struct(productions[0], fields),
+                }
+            } else {
+                let mut used = HashSet::new();
+                let mut variants = Vec::new();
+                for (alternative, &production) in alternatives.into_iter().zip(productions) {
+                    let sequence = sequence(alternative);
+                    let fields = fields(alternative)?;
+                    let content = match sequence.as_slice() {
+                        _ if !fields.is_empty() => Content::Fields(fields),
+                        [Pattern::Reference(id, None)] => Content::Single(target(*id, &indices)),
+                        _ => Content::Unit,
+                    };
+
+                    let base = variant(&sequence, store);
+                    let mut name = base.clone();
+                    let mut n = 1;
+                    while !used.insert(name.clone()) {
+                        n += 1;
+                        name = format!("{}{}", base, n);
+                    }
+
+                    variants.push(Variant { name, production, content });
+                }
+                Shape::Enum(variants)
+            };
+
+            types.push(Type {
+                name: ident(&store[def.name]),
+                lifetime: false,
+                shape,
+            });
+        }
+
+        // type has lifetime if it holds token, or type which has lifetime
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..types.len() {
+                let needs = |target: &Target| match *target {
+                    Target::Token => true,
+                    Target::Rule(rule) => types[rule].lifetime,
+                };
+                let lifetime = match &types[i].shape {
+                    Shape::Struct(_, fields) => fields.iter().any(|field| needs(&field.target)),
+                    Shape::Single(_, target) => needs(target),
+                    Shape::Enum(variants) => variants.iter().any(|variant| match &variant.content {
+                        Content::Fields(fields) => fields.iter().any(|field| needs(&field.target)),
+                        Content::Single(target) => needs(target),
+                        Content::Unit => false,
+                    }),
+                };
+                if lifetime && !types[i].lifetime {
+                    types[i].lifetime = true;
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(Self { types })
+    }
+
+    pub fn actions(&self) -> Actions<'_> {
+        Actions { ast: self }
+    }
+
+    // of type of rule
+    pub(super) fn ident(&self, rule: usize) -> &str {
+        &self.types[rule].name
+    }
+
+    // `Value<'a>`
+    fn name(&self, rule: usize) -> String {
+        let ty = &self.types[rule];
+        match ty.lifetime {
+            true => format!("{}<'a>", ty.name),
+            false => ty.name.clone(),
+        }
+    }
+
+    fn ty(&self, target: Target, count: Count) -> String {
+        match (target, count) {
+            (Target::Token, Count::One) => "&'a str".to_string(),
+            (Target::Token, Count::Optional) => "Option<&'a str>".to_string(),
+            (Target::Token, Count::Many) => "Vec<&'a str>".to_string(),
+            (Target::Rule(rule), Count::One) => format!("Box<{}>", self.name(rule)),
+            (Target::Rule(rule), Count::Optional) => format!("Option<Box<{}>>", self.name(rule)),
+            (Target::Rule(rule), Count::Many) => format!("Vec<{}>", self.name(rule)),
+        }
+    }
+
+    // value of `__child`
+    fn value(&self, target: Target, count: Count) -> String {
+        match (target, count) {
+            (Target::Token, _) => "__child.token().unwrap().value()".to_string(),
+            (Target::Rule(rule), Count::Many) => format!("{}::from(__child)", self.types[rule].name),
+            (Target::Rule(rule), _) => format!("Box::new({}::from(__child))", self.types[rule].name),
+        }
+    }
+
+    // fields of variants share visibility of their enum
+    fn fields(&self, f: &mut Formatter<'_>, fields: &[Field], vis: &str, indent: usize) -> std::fmt::Result {
+        for field in fields {
+            writeln!(f, "{:indent$}{}{}: {},", "", vis, field.name, self.ty(field.target, field.count))?;
+        }
+        Ok(())
+    }
+
+    // `constructor` of captures among children of `__node`
+    fn collect(&self, f: &mut Formatter<'_>, constructor: &str, fields: &[Field], indent: usize) -> std::fmt::Result {
+        for field in fields {
+            let init = if field.count == Count::Many { "Vec::new()" } else { "None" };
+            writeln!(f, "{:indent$}let mut {} = {};", "", field.name, init)?;
+        }
+
+        writeln!(f, "{:indent$}for __child in __node.children() {{", "")?;
+        writeln!(f, "{:indent$}    match __child.capture() {{", "")?;
+        for field in fields {
+            let value = self.value(field.target, field.count);
+            let assign = match field.count {
+                Count::Many => format!("{}.push({})", field.name, value),
+                _ => format!("{} = Some({})", field.name, value),
+            };
+            writeln!(f, "{:indent$}        Some({:?}) => {},", "", field.capture, assign)?;
+        }
+        writeln!(f, "{:indent$}        _ => {{}}", "")?;
+        writeln!(f, "{:indent$}    }}", "")?;
+        writeln!(f, "{:indent$}}}", "")?;
+        writeln!(f)?;
+
+        writeln!(f, "{:indent$}{} {{", "", constructor)?;
+        for field in fields {
+            match field.count {
+                Count::One => writeln!(f, "{:indent$}    {}: {}.unwrap(),", "", field.name, field.name)?,
+                _ => writeln!(f, "{:indent$}    {},", "", field.name)?,
+            }
+        }
+        writeln!(f, "{:indent$}}}", "")
+    }
+}
+
+fn target(id: usize, indices: &HashMap<usize, usize>) -> Target {
+    match indices.get(&id) {
+        Some(&rule) => Target::Rule(rule),
+        None => Target::Token,
+    }
+}
+
+fn field((name, target, min, max): (usize, Target, usize, usize), store: &Store<Cow<str>>) -> Field {
+    let count = match (min, max) {
+        (1, 1) => Count::One,
+        (_, 0 | 1) => Count::Optional,
+        _ => Count::Many,
+    };
+
+    Field {
+        name: ident(&store[name]),
+        capture: store[name].to_string(),
+        target,
+        count,
+    }
+}
+
+fn captures(pat: &Pattern, indices: &HashMap<usize, usize>, store: &Store<Cow<str>>) -> Result<Captures, ErrorKind> {
+    let check = |a: Target, b: Target, name: usize| match a == b {
+        true => Ok(()),
+        false => Err(ErrorKind::CaptureMismatch(store[name].to_string())),
+    };
+
+    Ok(match pat {
+        Pattern::Reference(id, Some(name)) => vec![(*name, target(*id, indices), 1, 1)],
+        Pattern::Reference(_, None)
+        | Pattern::Literal(_)
+        | Pattern::Class(_)
+        | Pattern::Prec(_)
+        | Pattern::Mode(_)
+        | Pattern::Code(_) => Vec::new(),
+        Pattern::Group(list) => {
+            let mut all: Captures = Vec::new();
+            for pat in list {
+                for (name, target, min, max) in captures(pat, indices, store)? {
+                    match all.iter_mut().find(|capture| capture.0 == name) {
+                        Some(capture) => {
+                            check(capture.1, target, name)?;
+                            capture.2 += min;
+                            capture.3 = capture.3.saturating_add(max);
+                        }
+                        None => all.push((name, target, min, max)),
+                    }
+                }
+            }
+            all
+        }
+        Pattern::Or(list, _) => {
+            let mut all: Captures = Vec::new();
+            for (i, pat) in list.iter().enumerate() {
+                let captures = captures(pat, indices, store)?;
+                for capture in &mut all {
+                    if !captures.iter().any(|other| other.0 == capture.0) {
+                        capture.2 = 0;
+                    }
+                }
+                for (name, target, min, max) in captures {
+                    match all.iter_mut().find(|capture| capture.0 == name) {
+                        Some(capture) => {
+                            check(capture.1, target, name)?;
+                            capture.2 = capture.2.min(min);
+                            capture.3 = capture.3.max(max);
+                        }
+                        None => all.push((name, target, if i == 0 { min } else { 0 }, max)),
+                    }
+                }
+            }
+            all
+        }
+        Pattern::Quantifier(quantifier) => {
+            let (n, m) = (*quantifier.range().start(), *quantifier.range().end());
+            let mut all = captures(quantifier.what(), indices, store)?;
+            for capture in &mut all {
+                capture.2 = capture.2.saturating_mul(n);
+                capture.3 = if m == usize::MAX && capture.3 > 0 { usize::MAX } else { capture.3.saturating_mul(m) };
+            }
+            all
+        }
+    })
+}
+
+// top-level symbols of alternative, without `%prec` or code
+fn sequence(pat: &Pattern) -> Vec<&Pattern> {
+    match pat {
+        Pattern::Group(list) => list.iter().filter(|pat| !matches!(pat, Pattern::Prec(_) | Pattern::Code(_))).collect(),
+        Pattern::Code(_) => Vec::new(),
+        pat => vec![pat],
+    }
+}
+
+// name of what alternative is, or of its first literal or reference
+fn variant(sequence: &[&Pattern], store: &Store<Cow<str>>) -> String {
+    if let [Pattern::Reference(id, _)] = sequence {
+        return ident(&store[*id]);
+    }
+
+    let literal = sequence.iter().find_map(|pat| match pat {
+        Pattern::Literal(literal) => Some(literal_variant(literal)),
+        _ => None,
+    });
+    let reference = sequence.iter().find_map(|pat| match pat {
+        Pattern::Reference(id, _) => Some(ident(&store[*id])),
+        _ => None,
+    });
+
+    literal.or(reference).unwrap_or_else(|| "Empty".to_string())
+}
+
+// module at indent of `generated` module
+impl Display for Ast {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "    pub mod ast {{")?;
+        for (i, ty) in self.types.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+
+            let name = self.name(i);
+            let (generics, node) = match ty.lifetime {
+                true => ("<'a>", "clr1::parse::Node<'a, '_, super::TokenKind>"),
+                false => ("", "clr1::parse::Node<'_, '_, super::TokenKind>"),
+            };
+
+            writeln!(f, "        #[derive(Debug)]")?;
+            match &ty.shape {
+                Shape::Struct(_, fields) if fields.is_empty() => writeln!(f, "        pub struct {};", name)?,
+                Shape::Struct(_, fields) => {
+                    writeln!(f, "        pub struct {} {{", name)?;
+                    self.fields(f, fields, "pub ", 12)?;
+                    writeln!(f, "        }}")?;
+                }
+                Shape::Single(_, target) => {
+                    writeln!(f, "        pub struct {}(pub {});", name, self.ty(*target, Count::One))?
+                }
+                Shape::Enum(variants) => {
+                    writeln!(f, "        pub enum {} {{", name)?;
+                    for variant in variants {
+                        match &variant.content {
+                            Content::Fields(fields) => {
+                                writeln!(f, "            {} {{", variant.name)?;
+                                self.fields(f, fields, "", 16)?;
+                                writeln!(f, "            }},")?;
+                            }
+                            Content::Single(target) => {
+                                writeln!(f, "            {}({}),", variant.name, self.ty(*target, Count::One))?
+                            }
+                            Content::Unit => writeln!(f, "            {},", variant.name)?,
+                        }
+                    }
+                    writeln!(f, "        }}")?;
+                }
+            }
+            writeln!(f)?;
+
+            writeln!(f, "        impl{} From<{}> for {} {{", generics, node, name)?;
+            match &ty.shape {
+                Shape::Struct(_, fields) if fields.is_empty() => {
+                    writeln!(f, "            fn from(_: {}) -> Self {{", node)?;
+                    writeln!(f, "                Self")?;
+                }
+                Shape::Struct(_, fields) => {
+                    writeln!(f, "            fn from(__node: {}) -> Self {{", node)?;
+                    self.collect(f, "Self", fields, 16)?;
+                }
+                Shape::Single(_, target) => {
+                    writeln!(f, "            fn from(__node: {}) -> Self {{", node)?;
+                    writeln!(f, "                let __child = __node.children().next().unwrap();")?;
+                    writeln!(f, "                Self({})", self.value(*target, Count::One))?;
+                }
+                Shape::Enum(variants) => {
+                    writeln!(f, "            fn from(__node: {}) -> Self {{", node)?;
+                    writeln!(f, "                match __node.production() {{")?;
+                    for variant in variants {
+                        let constructor = format!("Self::{}", variant.name);
+                        match &variant.content {
+                            Content::Fields(fields) => {
+                                writeln!(f, "                    Some({}) => {{", variant.production)?;
+                                self.collect(f, &constructor, fields, 24)?;
+                                writeln!(f, "                    }}")?;
+                            }
+                            Content::Single(target) => {
+                                let value = self.value(*target, Count::One);
+                                writeln!(f, "                    Some({}) => {{", variant.production)?;
+                                let child = "let __child = __node.children().next().unwrap();";
+                                writeln!(f, "                        {}", child)?;
+                                writeln!(f, "                        {}({})", constructor, value)?;
+                                writeln!(f, "                    }}")?;
+                            }
+                            Content::Unit => {
+                                writeln!(f, "                    Some({}) => {},", variant.production, constructor)?
+                            }
+                        }
+                    }
+                    writeln!(f, "                    _ => unreachable!(\"node is not of {}\"),", ty.name)?;
+                    writeln!(f, "                }}")?;
+                }
+            }
+            writeln!(f, "            }}")?;
+            writeln!(f, "        }}")?;
+        }
+        write!(f, "    }}")
+    }
+}
+
+// what `reduce_*` method is given
+pub(super) enum Params<'f> {
+    Fields(&'f [Field]),
+    // value of rule or alternative which is single reference without capture
+    Single(Target),
+}
+
+// `Actions` trait of generated code, with `reduce_*` method per production of rule, given its captures
+pub struct Actions<'a> {
+    ast: &'a Ast,
+}
+
+impl Actions<'_> {
+    // [rule, method name, production, params] in order of productions
+    pub(super) fn methods(&self) -> Vec<(usize, String, usize, Params<'_>)> {
+        let mut methods = Vec::new();
+        for (rule, ty) in self.ast.types.iter().enumerate() {
+            match &ty.shape {
+                Shape::Struct(production, fields) => {
+                    methods.push((rule, format!("reduce_{}", ty.name), *production, Params::Fields(fields)))
+                }
+                Shape::Single(production, target) => {
+                    methods.push((rule, format!("reduce_{}", ty.name), *production, Params::Single(*target)))
+                }
+                Shape::Enum(variants) => {
+                    for variant in variants {
+                        let params = match &variant.content {
+                            Content::Fields(fields) => Params::Fields(fields),
+                            Content::Single(target) => Params::Single(*target),
+                            Content::Unit => Params::Fields(&[]),
+                        };
+                        let name = format!("reduce_{}_{}", ty.name, variant.name);
+                        methods.push((rule, name, variant.production, params));
+                    }
+                }
+            }
+        }
+        methods.sort_by_key(|method| method.2);
+        methods
+    }
+}
+
+// `, name: Type` of each param
+pub(super) fn signature(params: &Params) -> String {
+    match params {
+        Params::Fields(fields) => {
+            fields.iter().map(|field| format!(", {}: {}", field.name, param(field.target, field.count))).collect()
+        }
+        Params::Single(target) => format!(", value: {}", param(*target, Count::One)),
+    }
+}
+
+fn param(target: Target, count: Count) -> String {
+    let ty = match target {
+        Target::Token => "Token<'a, TokenKind>",
+        Target::Rule(_) => "Self::Value",
+    };
+    match count {
+        Count::One => ty.to_string(),
+        Count::Optional => format!("Option<{}>", ty),
+        Count::Many => format!("Vec<{}>", ty),
+    }
+}
+
+// of `__value`
+fn unwrap(target: Target) -> &'static str {
+    match target {
+        Target::Token => "__value.token()",
+        Target::Rule(_) => "__value.reduced()",
+    }
+}
+
+// trait and its dispatch at indent of `generated` module
+impl Display for Actions<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let methods = self.methods();
+
+        writeln!(f, "    #[allow(non_snake_case)]")?;
+        writeln!(f, "    pub trait Actions<'a> {{")?;
+        writeln!(f, "        // what rules are reduced to")?;
+        writeln!(f, "        type Value;")?;
+        writeln!(f)?;
+        for (_, name, _, params) in &methods {
+            writeln!(f, "        fn {}(&mut self{}) -> Self::Value;", name, signature(params))?;
+            writeln!(f)?;
+        }
+        writeln!(f, "        // calls `reduce_*` as productions are reduced; returns what start rule was reduced to")?;
+        writeln!(f, "        fn parse(")?;
+        writeln!(f, "            &mut self,")?;
+        writeln!(f, "            tokens: impl IntoIterator<Item = Token<'a, TokenKind>>,")?;
+        writeln!(f, "        ) -> Result<Self::Value, Error<TokenKind>> {{")?;
+        writeln!(f, "            PARSER.parse_with(tokens, |production, values| reduce(self, production, values))")?;
+        writeln!(f, "        }}")?;
+        writeln!(f)?;
+        writeln!(f, "        // `parse`, recovering from errors by `error` of grammar; `None` if it could not")?;
+        writeln!(f, "        fn parse_recover(")?;
+        writeln!(f, "            &mut self,")?;
+        writeln!(f, "            tokens: impl IntoIterator<Item = Token<'a, TokenKind>>,")?;
+        writeln!(f, "        ) -> (Option<Self::Value>, Vec<Error<TokenKind>>) {{")?;
+        writeln!(f, "            PARSER.parse_with_recover(tokens, |production, values| {{")?;
+        writeln!(f, "                reduce(self, production, values)")?;
+        writeln!(f, "            }})")?;
+        writeln!(f, "        }}")?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    fn reduce<'a, A: Actions<'a> + ?Sized>(")?;
+        writeln!(f, "        __actions: &mut A,")?;
+        writeln!(f, "        __production: usize,")?;
+        writeln!(f, "        __values: Values<'a, TokenKind, A::Value>,")?;
+        writeln!(f, "    ) -> A::Value {{")?;
+        writeln!(f, "        match __production {{")?;
+        for (_, name, production, params) in &methods {
+            match params {
+                Params::Fields([]) => writeln!(f, "            {} => __actions.{}(),", production, name)?,
+                Params::Fields(fields) => {
+                    writeln!(f, "            {} => {{", production)?;
+                    for field in *fields {
+                        let init = if field.count == Count::Many { "Vec::new()" } else { "None" };
+                        writeln!(f, "                let mut {} = {};", field.name, init)?;
+                    }
+
+                    writeln!(f, "                for (__capture, __value) in __values {{")?;
+                    writeln!(f, "                    match __capture {{")?;
+                    for field in *fields {
+                        let assign = match field.count {
+                            Count::Many => format!("{}.push({})", field.name, unwrap(field.target)),
+                            _ => format!("{} = Some({})", field.name, unwrap(field.target)),
+                        };
+                        writeln!(f, "                        Some({:?}) => {},", field.capture, assign)?;
+                    }
+                    writeln!(f, "                        _ => {{}}")?;
+                    writeln!(f, "                    }}")?;
+                    writeln!(f, "                }}")?;
+                    writeln!(f)?;
+
+                    let args = fields
+                        .iter()
+                        .map(|field| match field.count {
+                            Count::One => format!("{}.unwrap()", field.name),
+                            _ => field.name.clone(),
+                        })
+                        .collect::<Vec<_>>();
+                    writeln!(f, "                __actions.{}({})", name, args.join(", "))?;
+                    writeln!(f, "            }}")?;
+                }
+                Params::Single(target) => {
+                    writeln!(f, "            {} => {{", production)?;
+                    writeln!(f, "                let (_, __value) = __values.into_iter().next().unwrap();")?;
+                    writeln!(f, "                __actions.{}({})", name, unwrap(*target))?;
+                    writeln!(f, "            }}")?;
+                }
+            }
+        }
+        writeln!(f, "            _ => unreachable!(\"production is not of rule\"),")?;
+        writeln!(f, "        }}")?;
+        write!(f, "    }}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::pattern::rules;
+
+    #[test]
+    fn test() {
+        let generate = |parse| {
+            let mut store = Store::new();
+            let mut lexer = Lexer::parse("Id : [a-z]+ ; Num : [0-9]+ ;", &mut store).unwrap();
+            let rules = rules(parse, &mut store).unwrap();
+            let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+            Ast::new(&rules, &grammar, &store).map(|ast| (ast.to_string(), ast.actions().to_string()))
+        };
+
+        let ast = generate(
+            r#"
+List : '[' Item@items* (',' Item@items)* ']' Id@tag? Unit@unit? ;
+Item : Id@name '=' Expr@value | Id@name | 'match' Id@type ;
+Expr : Num@lhs '+' Expr@rhs | Num | '(' Expr@inner ')' | 'x' ;
+Unit : 'u' ;
+            "#,
+        );
+        let (ast, actions) = ast.unwrap();
+        assert!(ast.contains("pub struct List<'a> {\n            pub items: Vec<Item<'a>>,"));
+        assert!(ast.contains("pub tag: Option<&'a str>,\n            pub unit: Option<Box<Unit>>,"));
+        assert!(ast.contains("Eq {\n                name: &'a str,\n                value: Box<Expr<'a>>,"));
+        assert!(ast.contains("Match {\n                r#type: &'a str,"));
+        assert!(ast.contains("Plus {") && ast.contains("Num(&'a str),") && ast.contains("LParen {"));
+        assert!(ast.contains("pub struct Unit;"));
+
+        let list = "items: Vec<Self::Value>, tag: Option<Token<'a, TokenKind>>, unit: Option<Self::Value>";
+        assert!(actions.contains(&format!("fn reduce_List(&mut self, {}) -> Self::Value;", list)));
+        assert!(actions.contains("fn reduce_Item_Match(&mut self, r#type: Token<'a, TokenKind>) -> Self::Value;"));
+        assert!(actions.contains("fn reduce_Expr_Num(&mut self, value: Token<'a, TokenKind>) -> Self::Value;"));
+        assert!(actions.contains("Some(\"items\") => items.push(__value.reduced()),"));
+        assert!(actions.contains("__actions.reduce_Item_Eq(name.unwrap(), value.unwrap())"));
+        assert!(actions.contains("=> __actions.reduce_Unit(),"));
+
+        // value of only symbol is passed on, whether rule has one alternative or many
+        let (ast, actions) = generate("Prog : Expr ; Expr : Id | Num ; Name : Id ;").unwrap();
+        assert!(ast.contains("pub struct Prog<'a>(pub Box<Expr<'a>>);"));
+        assert!(ast.contains("pub struct Name<'a>(pub &'a str);"));
+        assert!(actions.contains("fn reduce_Prog(&mut self, value: Self::Value) -> Self::Value;"));
+        assert!(actions.contains("fn reduce_Name(&mut self, value: Token<'a, TokenKind>) -> Self::Value;"));
+        assert!(actions.contains("fn reduce_Expr_Id(&mut self, value: Token<'a, TokenKind>) -> Self::Value;"));
+        assert!(actions.contains("__actions.reduce_Prog(__value.reduced())"));
+
+        let error = generate("S : (Id@x | T@x) ';' ; T : Num ;").err().unwrap().to_string();
+        assert!(error.ends_with("capture 'x' refers to different rules"), "{}", error);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::parse::analysis::Analysis;
+use crate::parse::grammar::{Grammar, Symbol};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+
+// [production, dot, lookahead]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Item {
+    production: usize,
+    dot: usize,
+    lookahead: usize,
+}
+
+impl Item {
+    pub fn new(production: usize, dot: usize, lookahead: usize) -> Self {
+        Self { production, dot, lookahead }
+    }
+
+    pub fn production(&self) -> usize {
+        self.production
+    }
+
+    pub fn dot(&self) -> usize {
+        self.dot
+    }
+
+    pub fn lookahead(&self) -> usize {
+        self.lookahead
+    }
+
+    pub fn next(&self, grammar: &Grammar) -> Option<Symbol> {
+        grammar.productions()[self.production].body().get(self.dot).copied()
+    }
+
+    fn advance(&self) -> Self {
+        Self::new(self.production, self.dot + 1, self.lookahead)
+    }
+}
+
+pub struct State {
+    items: Vec<Item>,
+    transitions: BTreeMap<Symbol, usize>,
+}
+
+impl State {
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn transitions(&self) -> &BTreeMap<Symbol, usize> {
+        &self.transitions
+    }
+}
+
+// canonical collection of LR(1) item sets
+pub struct Automaton<'g> {
+    grammar: &'g Grammar,
+    states: Vec<State>,
+}
+
+impl<'g> Automaton<'g> {
+    pub fn new(analysis: &Analysis<'g>) -> Self {
+        let grammar = analysis.grammar();
+
+        let initial = closure(analysis, vec![Item::new(0, 0, Grammar::END)]);
+        let mut states = vec![State {
+            items: initial,
+            transitions: BTreeMap::new(),
+        }];
+
+        let mut kernels: HashMap<Vec<Item>, usize> = HashMap::new();
+
+        let mut i = 0;
+        while i < states.len() {
+            let mut gotos: BTreeMap<Symbol, Vec<Item>> = BTreeMap::new();
+            for item in &states[i].items {
+                if let Some(symbol) = item.next(grammar) {
+                    gotos.entry(symbol).or_default().push(item.advance());
+                }
+            }
+
+            for (symbol, kernel) in gotos {
+                let target = match kernels.get(&kernel) {
+                    Some(&target) => target,
+                    None => {
+                        let target = states.len();
+                        states.push(State {
+                            items: closure(analysis, kernel.clone()),
+                            transitions: BTreeMap::new(),
+                        });
+                        kernels.insert(kernel, target);
+                        target
+                    }
+                };
+
+                states[i].transitions.insert(symbol, target);
+            }
+
+            i += 1;
+        }
+
+        Self { grammar, states }
+    }
+
+    pub fn grammar(&self) -> &'g Grammar {
+        self.grammar
+    }
+
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+
+    // merges states of same block into one; states of a block share core, so transitions agree
+    pub fn merge(&self, blocks: &[usize]) -> Self {
+        let count = blocks.iter().max().map_or(0, |&max| max + 1);
+
+        let mut items = vec![BTreeSet::new(); count];
+        let mut transitions = vec![BTreeMap::new(); count];
+        for (state, &block) in self.states.iter().zip(blocks) {
+            items[block].extend(state.items.iter().copied());
+            for (&symbol, &target) in &state.transitions {
+                transitions[block].insert(symbol, blocks[target]);
+            }
+        }
+
+        let states = items
+            .into_iter()
+            .zip(transitions)
+            .map(|(items, transitions)| State {
+                items: items.into_iter().collect(),
+                transitions,
+            })
+            .collect();
+
+        Self {
+            grammar: self.grammar,
+            states,
+        }
+    }
+}
+
+fn closure(analysis: &Analysis, kernel: Vec<Item>) -> Vec<Item> {
+    let grammar = analysis.grammar();
+
+    let mut items: BTreeSet<Item> = kernel.iter().copied().collect();
+    let mut queue = kernel;
+
+    while let Some(item) = queue.pop() {
+        let Some(Symbol::Nonterminal(next)) = item.next(grammar) else {
+            continue;
+        };
+
+        let rest = &grammar.productions()[item.production].body()[item.dot + 1..];
+
+        let mut lookaheads = BTreeSet::new();
+        if analysis.first_of(rest, &mut lookaheads) {
+            lookaheads.insert(item.lookahead);
+        }
+
+        for &production in grammar.nonterminals()[next].productions() {
+            for &lookahead in &lookaheads {
+                let item = Item::new(production, 0, lookahead);
+                if items.insert(item) {
+                    queue.push(item);
+                }
+            }
+        }
+    }
+
+    items.into_iter().collect()
+}
+
+impl Display for Automaton<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, state) in self.states.iter().enumerate() {
+            writeln!(f, "State {}", i)?;
+
+            for item in &state.items {
+                writeln!(
+                    f,
+                    "    {}, {}",
+                    self.grammar.dotted(item.production, item.dot),
+                    self.grammar.name(Symbol::Terminal(item.lookahead))
+                )?;
+            }
+
+            for (&symbol, &target) in &state.transitions {
+                writeln!(f, "    {} => {}", self.grammar.name(symbol), target)?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::pattern::rules;
+    use crate::store::Store;
+
+    #[test]
+    fn test() {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse("c : 'c' ; d : 'd' ;", &mut store).unwrap();
+
+        // dragon book 4.54
+        let rules = rules(
+            r#"
+S : C C ;
+C : c C | d ;
+            "#,
+            &mut store,
+        )
+        .unwrap();
+
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        let automaton = Automaton::new(&Analysis::new(&grammar));
+
+        assert_eq!(automaton.states().len(), 10);
+
+        // states 1 and 6 (I3 and I6 of the book) have same core, but distinct lookaheads
+        let automaton = automaton.to_string();
+        let state = |i: usize| automaton.split("\n\n").nth(i).unwrap().to_string();
+        assert_eq!(
+            state(0),
+            "State 0\n    $accept -> . S, $\n    S -> . C C, $\n    C -> . c C, c\n    C -> . c C, d\n    \
+             C -> . d, c\n    C -> . d, d\n    c => 1\n    d => 2\n    S => 3\n    C => 4"
+        );
+        assert_eq!(
+            state(1),
+            "State 1\n    C -> . c C, c\n    C -> . c C, d\n    C -> c . C, c\n    C -> c . C, d\n    \
+             C -> . d, c\n    C -> . d, d\n    c => 1\n    d => 2\n    C => 5"
+        );
+        assert_eq!(
+            state(6),
+            "State 6\n    C -> . c C, $\n    C -> c . C, $\n    C -> . d, $\n    c => 6\n    d => 7\n    C => 9"
+        );
+        assert_eq!(state(8), "State 8\n    S -> C C ., $");
+    }
+}
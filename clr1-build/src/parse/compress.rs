@@ -0,0 +1,157 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem::size_of;
+
+// row-displaced (comb-vector) table; mirrors `clr1::parse::Table`.
+// rows with same default and same entries share one packed row, and entries equal to default are not stored.
+// entry at [row, column] is `entries[base[r] + column]` if `check[base[r] + column] == r`, otherwise `default[r]`,
+// where `r = rows[row]`.
+#[derive(Serialize)]
+pub struct Table<T> {
+    rows: Vec<usize>,
+    base: Vec<usize>,
+    default: Vec<T>,
+    // packed row owning each slot, number of packed rows if free
+    check: Vec<usize>,
+    entries: Vec<T>,
+    #[serde(skip)]
+    columns: usize,
+}
+
+impl<T: Copy + Eq + Hash> Table<T> {
+    // `default` picks default entry of row, and `kept` tells whether entry other than it must still be stored
+    pub fn new(
+        dense: &[Vec<T>],
+        default: impl Fn(&[T]) -> T,
+        kept: impl Fn(usize, usize, T) -> bool,
+    ) -> Self {
+        let columns = dense.first().map_or(0, |row| row.len());
+
+        let mut packed: HashMap<(T, Vec<(usize, T)>), usize> = HashMap::new();
+        let mut uniques = Vec::new();
+        let rows = dense
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let default = default(row);
+                let entries = row
+                    .iter()
+                    .enumerate()
+                    .filter(|&(column, &entry)| entry != default && kept(i, column, entry))
+                    .map(|(column, &entry)| (column, entry))
+                    .collect::<Vec<_>>();
+
+                let key = (default, entries);
+                *packed.entry(key.clone()).or_insert_with(|| {
+                    uniques.push(key);
+                    uniques.len() - 1
+                })
+            })
+            .collect();
+
+        // densest rows first, each at lowest base where its entries fit in free slots
+        let mut order = (0..uniques.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&r| std::cmp::Reverse(uniques[r].1.len()));
+
+        let free = uniques.len();
+        let mut base = vec![0; uniques.len()];
+        let mut check: Vec<usize> = Vec::new();
+        let mut entries: Vec<T> = Vec::new();
+        for r in order {
+            let (filler, list) = &uniques[r];
+            if list.is_empty() {
+                continue;
+            }
+
+            let fits = |b: usize| list.iter().all(|&(column, _)| check.get(b + column).is_none_or(|&c| c == free));
+            let b = (0..).find(|&b| fits(b)).unwrap();
+
+            for &(column, entry) in list {
+                if check.len() <= b + column {
+                    check.resize(b + column + 1, free);
+                    entries.resize(b + column + 1, *filler);
+                }
+                check[b + column] = r;
+                entries[b + column] = entry;
+            }
+            base[r] = b;
+        }
+
+        Self {
+            rows,
+            base,
+            default: uniques.into_iter().map(|(default, _)| default).collect(),
+            check,
+            entries,
+            columns,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, row: usize, column: usize) -> T {
+        let r = self.rows[row];
+        let slot = self.base[r] + column;
+        match self.check.get(slot) {
+            Some(&c) if c == r => self.entries[slot],
+            _ => self.default[r],
+        }
+    }
+
+    // bytes of statics, as uncompressed `&[&[T]]` and as packed
+    pub fn size(&self) -> (usize, usize) {
+        let slice = size_of::<&[T]>();
+        let dense = self.rows.len() * (slice + self.columns * size_of::<T>());
+
+        let usizes = self.rows.len() + self.base.len() + self.check.len();
+        let packed = usizes * size_of::<usize>() + (self.default.len() + self.entries.len()) * size_of::<T>();
+
+        (dense, packed)
+    }
+}
+
+// most frequent entry of row satisfying `pred`
+pub fn most<T: Copy + Eq + Hash>(row: &[T], pred: impl Fn(T) -> bool) -> Option<T> {
+    let mut counts: HashMap<T, usize> = HashMap::new();
+    for &entry in row {
+        if pred(entry) {
+            *counts.entry(entry).or_default() += 1;
+        }
+    }
+
+    // first in row among most frequent, so that result does not depend on hash order
+    let max = counts.values().copied().max()?;
+    row.iter().copied().find(|entry| counts.get(entry) == Some(&max))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let dense = vec![
+            vec![0, 1, 0, 0, 2],
+            vec![3, 3, 3, 0, 3],
+            vec![0, 1, 0, 0, 2],
+            vec![0, 0, 0, 0, 0],
+            vec![4, 0, 5, 0, 0],
+        ];
+
+        let table = Table::new(&dense, |row| most(row, |_| true).unwrap(), |_, _, _| true);
+        for (i, row) in dense.iter().enumerate() {
+            for (j, &entry) in row.iter().enumerate() {
+                assert_eq!(table.get(i, j), entry);
+            }
+        }
+
+        // rows 0 and 2 share one
+        assert_eq!(table.default.len(), 4);
+        assert_eq!(table.rows[0], table.rows[2]);
+        // [_, 1, _, _, 2], [4, _, 5] and [_, _, _, 0] interleave
+        assert_eq!(table.check.len(), 5);
+
+        let (dense, packed) = table.size();
+        assert!(packed < dense);
+    }
+}
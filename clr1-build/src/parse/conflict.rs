@@ -0,0 +1,193 @@
+use crate::error::{Error, ErrorKind};
+use crate::iter::Offset;
+use crate::parse::analysis::Analysis;
+use crate::parse::automaton::{Automaton, Item};
+use crate::parse::counterexample::Counterexample;
+use crate::parse::grammar::{Grammar, Symbol};
+use std::fmt::{Display, Formatter};
+
+// how shift/reduce conflict was settled by precedence
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    Shift,
+    Reduce,
+    // `%nonassoc`
+    Error,
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resolution::Shift => write!(f, "shift"),
+            Resolution::Reduce => write!(f, "reduce"),
+            Resolution::Error => write!(f, "error"),
+        }
+    }
+}
+
+pub struct Conflict {
+    state: usize,
+    lookahead: usize,
+    // items which shift lookahead
+    shifts: Vec<Item>,
+    // items which reduce on lookahead
+    reduces: Vec<Item>,
+    resolution: Option<Resolution>,
+}
+
+impl Conflict {
+    pub fn new(
+        state: usize,
+        lookahead: usize,
+        shifts: Vec<Item>,
+        reduces: Vec<Item>,
+        resolution: Option<Resolution>,
+    ) -> Self {
+        Self {
+            state,
+            lookahead,
+            shifts,
+            reduces,
+            resolution,
+        }
+    }
+
+    pub fn resolution(&self) -> Option<Resolution> {
+        self.resolution
+    }
+
+    fn at(&self, grammar: &Grammar) -> Offset {
+        let head = grammar.productions()[self.reduces[0].production()].head();
+        grammar.nonterminals()[head].at()
+    }
+
+    fn items(&self, grammar: &Grammar) -> String {
+        self.shifts
+            .iter()
+            .chain(&self.reduces)
+            .map(|item| {
+                let head = grammar.productions()[item.production()].head();
+                let at = grammar.nonterminals()[head].at();
+                format!("\n    {}: {}", at, grammar.dotted(item.production(), item.dot()))
+            })
+            .collect()
+    }
+
+    // note on conflict which precedence settled, `None` if it is left to be reported as error
+    pub fn report(&self, automaton: &Automaton) -> Option<String> {
+        let grammar = automaton.grammar();
+        let resolution = self.resolution?;
+
+        Some(format!(
+            "{}: shift/reduce conflict in state {} on {} resolved as {} by precedence:{}",
+            self.at(grammar),
+            self.state,
+            grammar.name(Symbol::Terminal(self.lookahead)),
+            resolution,
+            self.items(grammar)
+        ))
+    }
+
+    pub fn error(&self, analysis: &Analysis, automaton: &Automaton) -> Error {
+        let grammar = automaton.grammar();
+        let items = self.items(grammar);
+
+        // one shift derivation is enough, as all shift items move to same state
+        let involved = self.shifts.iter().take(1).chain(&self.reduces).copied().collect::<Vec<_>>();
+        let example = Counterexample::new(analysis, automaton, self.state, self.lookahead, &involved).to_string();
+
+        let state = self.state;
+        let lookahead = grammar.name(Symbol::Terminal(self.lookahead)).to_string();
+
+        let kind = if self.shifts.is_empty() {
+            ErrorKind::ReduceReduceConflict { state, lookahead, items, example }
+        } else {
+            ErrorKind::ShiftReduceConflict { state, lookahead, items, example }
+        };
+
+        kind.at(self.at(grammar))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lex::Lexer;
+    use crate::parse::{Analysis, Automaton, Grammar, Parser};
+    use crate::pattern::rules;
+    use crate::store::Store;
+
+    fn conflicts(parse: &str) -> Vec<String> {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse("Id : [a-z]+ ;", &mut store).unwrap();
+
+        let rules = rules(parse, &mut store).unwrap();
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        let analysis = Analysis::new(&grammar);
+        let automaton = Automaton::new(&analysis);
+
+        let (_, conflicts) = Parser::new(&automaton);
+        conflicts
+            .iter()
+            .map(|conflict| match conflict.report(&automaton) {
+                Some(report) => report,
+                None => conflict.error(&analysis, &automaton).to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test() {
+        let errors = conflicts("E : E '+' E | Id ;");
+        assert_eq!(
+            errors,
+            [r#"(1,1): shift/reduce conflict in state 4 on '+':
+    (1,1): E -> E . '+' E
+    (1,1): E -> E '+' E .
+  ambiguous example: Id '+' Id • '+' Id
+    shift derivation: E ::= [ E '+' E ::= [ E • '+' E ] ]
+    reduce derivation: E ::= [ E ::= [ E '+' E • ] '+' E ]"#]
+        );
+
+        let errors = conflicts(
+            r#"
+S : A | B ;
+A : Id ;
+B : Id ;
+            "#,
+        );
+        assert_eq!(
+            errors,
+            [r#"(3,1): reduce/reduce conflict in state 1 on $:
+    (3,1): A -> Id .
+    (4,1): B -> Id .
+  ambiguous example: Id •
+    reduce derivation: S ::= [ A ::= [ Id • ] ]
+    reduce derivation: S ::= [ B ::= [ Id • ] ]"#]
+        );
+
+        let reports = conflicts(
+            r#"
+%nonassoc '==' ;
+%left '+' '-' ;
+%left '*' ;
+%right Neg ;
+
+E : E '==' E | E '+' E | E '-' E | E '*' E | '-' E %prec Neg | Id ;
+            "#,
+        );
+        assert_eq!(reports.len(), 20);
+        assert!(reports.iter().all(|report| report.contains("by precedence")));
+        assert!(reports.contains(
+            &r#"(7,1): shift/reduce conflict in state 9 on '==' resolved as error by precedence:
+    (7,1): E -> E . '==' E
+    (7,1): E -> E '==' E ."#
+                .to_string()
+        ));
+        assert!(reports.contains(
+            &r#"(7,1): shift/reduce conflict in state 10 on '*' resolved as shift by precedence:
+    (7,1): E -> E . '*' E
+    (7,1): E -> E '+' E ."#
+                .to_string()
+        ));
+    }
+}
@@ -0,0 +1,598 @@
+use crate::parse::analysis::Analysis;
+use crate::parse::automaton::{Automaton, Item};
+use crate::parse::grammar::{Grammar, Symbol};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+
+// derivation of conflicting item from start symbol, as chain of [production, dot] from `$accept` inward.
+// each production but last is entered at its dot, last has its dot where the conflict is.
+struct Derivation {
+    label: &'static str,
+    frames: Vec<(usize, usize)>,
+}
+
+impl Derivation {
+    // sentential form split at conflict
+    fn form(&self, grammar: &Grammar) -> (Vec<Symbol>, Vec<Symbol>) {
+        let mut prefix = Vec::new();
+        let mut suffix = Vec::new();
+
+        for (i, &(production, dot)) in self.frames.iter().enumerate().rev() {
+            let body = grammar.productions()[production].body();
+            if i == self.frames.len() - 1 {
+                suffix.extend_from_slice(&body[dot..]);
+            } else {
+                suffix.extend_from_slice(&body[dot + 1..]);
+            }
+        }
+
+        for &(production, dot) in &self.frames {
+            prefix.extend_from_slice(&grammar.productions()[production].body()[..dot]);
+        }
+
+        (prefix, suffix)
+    }
+
+    // `A ::= [ x B ::= [ y • z ] w ]`
+    fn render(&self, grammar: &Grammar) -> String {
+        let mut out = String::new();
+        // `$accept` is not shown
+        let frames = &self.frames[1..];
+
+        for (i, &(production, dot)) in frames.iter().enumerate() {
+            let production = &grammar.productions()[production];
+            out.push_str(grammar.name(Symbol::Nonterminal(production.head())));
+            out.push_str(" ::= [");
+
+            for &symbol in &production.body()[..dot] {
+                out.push(' ');
+                out.push_str(grammar.name(symbol));
+            }
+            if i == frames.len() - 1 {
+                out.push_str(" •");
+                for &symbol in &production.body()[dot..] {
+                    out.push(' ');
+                    out.push_str(grammar.name(symbol));
+                }
+            } else {
+                out.push(' ');
+            }
+        }
+
+        for (i, &(production, dot)) in frames.iter().enumerate().rev() {
+            if i != frames.len() - 1 {
+                for &symbol in &grammar.productions()[production].body()[dot + 1..] {
+                    out.push(' ');
+                    out.push_str(grammar.name(symbol));
+                }
+            }
+            out.push_str(" ]");
+        }
+
+        out
+    }
+}
+
+// example input and derivations leading into conflict, in the manner of bison's `-Wcounterexamples`.
+// if every derivation yields same sentential form, the grammar is ambiguous and one example suffices.
+pub struct Counterexample<'g> {
+    grammar: &'g Grammar,
+    derivations: Vec<Derivation>,
+    // one per derivation, or one for all if unifying
+    examples: Vec<String>,
+}
+
+impl<'g> Counterexample<'g> {
+    pub fn new(
+        analysis: &Analysis<'g>,
+        automaton: &Automaton<'g>,
+        state: usize,
+        lookahead: usize,
+        items: &[Item],
+    ) -> Self {
+        let grammar = analysis.grammar();
+        let expansion = Expansion::new(analysis);
+
+        // first two items searched together for unifying example; the rest, or all if there is none, one by one
+        let walk = Walk::new(analysis, automaton);
+        let mut joint = match items {
+            [a, b, ..] => walk.joint(state, *a, *b).map(Vec::from),
+            _ => None,
+        }
+        .unwrap_or_default()
+        .into_iter();
+
+        let derivations = items
+            .iter()
+            .filter_map(|&item| {
+                let label = if item.next(grammar).is_some() { "shift" } else { "reduce" };
+                let frames = joint.next().or_else(|| walk.search(state, item))?;
+                Some(Derivation { label, frames })
+            })
+            .collect::<Vec<_>>();
+
+        let forms = derivations.iter().map(|d| d.form(grammar)).collect::<Vec<_>>();
+        let unifying = forms.windows(2).all(|pair| pair[0] == pair[1]);
+
+        let examples = forms
+            .iter()
+            .take(if unifying { 1 } else { forms.len() })
+            .map(|(prefix, suffix)| {
+                let name = |&terminal: &usize| grammar.name(Symbol::Terminal(terminal));
+
+                let prefix = expansion.expand(prefix, None);
+                let suffix = expansion.expand(suffix, Some(lookahead));
+
+                let example = prefix.iter().map(name).chain(["•"]).chain(suffix.iter().map(name));
+                example.collect::<Vec<_>>().join(" ")
+            })
+            .collect();
+
+        Self {
+            grammar,
+            derivations,
+            examples,
+        }
+    }
+}
+
+impl Display for Counterexample<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.derivations.is_empty() {
+            return Ok(());
+        }
+
+        if self.examples.len() == 1 {
+            write!(f, "\n  ambiguous example: {}", self.examples[0])?;
+            for derivation in &self.derivations {
+                write!(f, "\n    {} derivation: {}", derivation.label, derivation.render(self.grammar))?;
+            }
+        } else {
+            for (derivation, example) in self.derivations.iter().zip(&self.examples) {
+                write!(f, "\n  {} example: {}", derivation.label, example)?;
+                write!(f, "\n    derivation: {}", derivation.render(self.grammar))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// nodes joint search visits before giving up on unifying example
+const LIMIT: usize = 50_000;
+
+// walks backward from items of conflict: either undoing a shift into predecessor state, or leaving production to
+// item of same state which predicted it, in which lookahead of left item is justified by what follows it
+struct Walk<'a, 'g> {
+    analysis: &'a Analysis<'g>,
+    automaton: &'a Automaton<'g>,
+    // states with transition into each state, and on which symbol
+    predecessors: Vec<Vec<(usize, Symbol)>>,
+}
+
+impl<'a, 'g> Walk<'a, 'g> {
+    fn new(analysis: &'a Analysis<'g>, automaton: &'a Automaton<'g>) -> Self {
+        let states = automaton.states();
+        let mut predecessors = vec![Vec::new(); states.len()];
+        for (from, state) in states.iter().enumerate() {
+            for (&symbol, &to) in state.transitions() {
+                predecessors[to].push((from, symbol));
+            }
+        }
+
+        Self {
+            analysis,
+            automaton,
+            predecessors,
+        }
+    }
+
+    fn start() -> (usize, Item) {
+        (0, Item::new(0, 0, Grammar::END))
+    }
+
+    // symbol before dot, and item with dot before it
+    fn unshifted(&self, item: Item) -> Option<(Symbol, Item)> {
+        let dot = item.dot().checked_sub(1)?;
+        let symbol = self.analysis.grammar().productions()[item.production()].body()[dot];
+        Some((symbol, Item::new(item.production(), dot, item.lookahead())))
+    }
+
+    // predecessors of `state` which have `item` and shift `symbol` into `state`
+    fn shifts<'w>(&'w self, state: usize, symbol: Symbol, item: Item) -> impl Iterator<Item = usize> + 'w {
+        let states = self.automaton.states();
+        self.predecessors[state]
+            .iter()
+            .filter(move |&&(from, via)| via == symbol && states[from].items().binary_search(&item).is_ok())
+            .map(|&(from, _)| from)
+    }
+
+    // items of `state` which predicted production of `item`, as it is at its start
+    fn leaves(&self, state: usize, item: Item) -> Vec<Item> {
+        let grammar = self.analysis.grammar();
+        let head = grammar.productions()[item.production()].head();
+
+        let mut parents = Vec::new();
+        for &parent in self.automaton.states()[state].items() {
+            if parent.next(grammar) != Some(Symbol::Nonterminal(head)) {
+                continue;
+            }
+
+            let mut first = BTreeSet::new();
+            if self.analysis.first_of(rest(grammar, parent), &mut first) {
+                first.insert(parent.lookahead());
+            }
+
+            if first.contains(&item.lookahead()) {
+                parents.push(parent);
+            }
+        }
+        parents
+    }
+
+    // shortest path of items within automaton from start item to conflicting item
+    fn search(&self, state: usize, item: Item) -> Option<Vec<(usize, usize)>> {
+        let start = Self::start();
+        let mut parents: HashMap<(usize, Item), (usize, Item)> = HashMap::new();
+        let mut queue = VecDeque::from([(state, item)]);
+
+        while let Some(node @ (state, item)) = queue.pop_front() {
+            if node == start {
+                break;
+            }
+
+            let mut prev = Vec::new();
+            if let Some((symbol, item)) = self.unshifted(item) {
+                prev.extend(self.shifts(state, symbol, item).map(|from| (from, item)));
+            } else {
+                prev.extend(self.leaves(state, item).into_iter().map(|parent| (state, parent)));
+            }
+
+            for prev in prev {
+                if prev != node && !parents.contains_key(&prev) {
+                    parents.insert(prev, node);
+                    queue.push_back(prev);
+                }
+            }
+        }
+
+        let mut path = vec![start.1];
+        let mut node = start;
+        while node != (state, item) {
+            node = *parents.get(&node)?;
+            path.push(node.1);
+        }
+
+        Some(frames(&path))
+    }
+
+    // shortest pair of paths from start item to `a` and `b` which derive same sentential form, walked backward
+    // together. as each prefix leads to one state, both undo same shifts in lockstep, and what either derives
+    // after conflict must go on to be what the other does. `None` if there is none, or none is found within
+    // `LIMIT` nodes.
+    fn joint(&self, state: usize, a: Item, b: Item) -> Option<[Vec<(usize, usize)>; 2]> {
+        let grammar = self.analysis.grammar();
+        let start = Self::start();
+
+        // shifting item may have any lookahead
+        let alike = |item: Item| -> Vec<Item> {
+            if item.next(grammar).is_none() {
+                return vec![item];
+            }
+            let core = |item: &Item| (item.production(), item.dot());
+            let items = self.automaton.states()[state].items().iter();
+            items.filter(|other| core(other) == core(&item)).copied().collect()
+        };
+
+        let mut parents: HashMap<Joint, Joint> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for &a in &alike(a) {
+            for &b in &alike(b) {
+                let node = Joint { state, items: [a, b], ahead: 0, owed: Vec::new() };
+                let node = node.derive(0, after(grammar, a)).and_then(|node| node.derive(1, after(grammar, b)));
+                if let Some(node) = node
+                    && !parents.contains_key(&node)
+                {
+                    parents.insert(node.clone(), node.clone());
+                    queue.push_back(node);
+                }
+            }
+        }
+
+        let mut visited = 0;
+        let goal = loop {
+            let node = queue.pop_front()?;
+            if node.items == [start.1; 2] && node.owed.is_empty() {
+                break node;
+            }
+
+            visited += 1;
+            if visited > LIMIT {
+                return None;
+            }
+
+            let mut prev = Vec::new();
+            if let (Some((x, a)), Some((y, b))) = (self.unshifted(node.items[0]), self.unshifted(node.items[1]))
+                && x == y
+            {
+                for from in self.shifts(node.state, x, a) {
+                    if self.shifts(node.state, y, b).any(|other| other == from) {
+                        prev.push(Joint { state: from, items: [a, b], ..node.clone() });
+                    }
+                }
+            }
+            for side in 0..2 {
+                if node.items[side].dot() > 0 {
+                    continue;
+                }
+                for parent in self.leaves(node.state, node.items[side]) {
+                    let mut items = node.items;
+                    items[side] = parent;
+                    prev.extend(Joint { items, ..node.clone() }.derive(side, rest(grammar, parent)));
+                }
+            }
+
+            for prev in prev {
+                if !parents.contains_key(&prev) {
+                    parents.insert(prev.clone(), node.clone());
+                    queue.push_back(prev);
+                }
+            }
+        };
+
+        // follow path forward; each side stays in place while the other leaves production
+        let mut paths = [vec![start.1], vec![start.1]];
+        let mut node = goal;
+        loop {
+            let next = &parents[&node];
+            if *next == node {
+                break;
+            }
+            node = next.clone();
+            for (path, &item) in paths.iter_mut().zip(&node.items) {
+                if *path.last().unwrap() != item {
+                    path.push(item);
+                }
+            }
+        }
+
+        Some(paths.map(|path| frames(&path)))
+    }
+}
+
+// node of joint search: items of both sides in same state, and symbols one side has derived after conflict
+// which the other has yet to
+#[derive(Clone, Eq, Hash, PartialEq)]
+struct Joint {
+    state: usize,
+    items: [Item; 2],
+    ahead: usize,
+    owed: Vec<Symbol>,
+}
+
+impl Joint {
+    // `side` derives `symbols` next; `None` if they differ from what the other side derived there
+    fn derive(mut self, side: usize, symbols: &[Symbol]) -> Option<Self> {
+        if self.ahead == side || self.owed.is_empty() {
+            self.ahead = side;
+            self.owed.extend_from_slice(symbols);
+            return Some(self);
+        }
+
+        let len = symbols.len().min(self.owed.len());
+        if symbols[..len] != self.owed[..len] {
+            return None;
+        }
+
+        self.owed.drain(..len);
+        if self.owed.is_empty() {
+            self.ahead = side;
+            self.owed.extend_from_slice(&symbols[len..]);
+        }
+        Some(self)
+    }
+}
+
+// symbols from dot on
+fn after(grammar: &Grammar, item: Item) -> &[Symbol] {
+    &grammar.productions()[item.production()].body()[item.dot()..]
+}
+
+// symbols after the one past dot, which follow nonterminal `item` predicts
+fn rest(grammar: &Grammar, item: Item) -> &[Symbol] {
+    &grammar.productions()[item.production()].body()[item.dot() + 1..]
+}
+
+// collapses shifts along path of items from start item, so that one frame remains per production entered
+fn frames(path: &[Item]) -> Vec<(usize, usize)> {
+    let mut frames = vec![(0, 0)];
+    for item in &path[1..] {
+        let last = frames.last_mut().unwrap();
+        if last.0 == item.production() && last.1 + 1 == item.dot() {
+            last.1 = item.dot();
+        } else {
+            frames.push((item.production(), item.dot()));
+        }
+    }
+    frames
+}
+
+// shortest terminal strings derivable from each nonterminal, optionally starting with given terminal
+struct Expansion {
+    shortest: Vec<Option<Vec<usize>>>,
+    // [nonterminal][terminal]
+    starting: Vec<Vec<Option<Vec<usize>>>>,
+}
+
+impl Expansion {
+    fn new(analysis: &Analysis) -> Self {
+        let grammar = analysis.grammar();
+        let terminals = grammar.terminals().len();
+        let nonterminals = grammar.nonterminals().len();
+
+        let mut expansion = Self {
+            shortest: vec![None; nonterminals],
+            starting: vec![vec![None; terminals]; nonterminals],
+        };
+
+        let better = |current: &Option<Vec<usize>>, candidate: &Vec<usize>| match current {
+            Some(current) => candidate.len() < current.len(),
+            None => true,
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for production in grammar.productions() {
+                let head = production.head();
+
+                if let Some(candidate) = expansion.expand_all(production.body())
+                    && better(&expansion.shortest[head], &candidate)
+                {
+                    expansion.shortest[head] = Some(candidate);
+                    changed = true;
+                }
+
+                for terminal in 0..terminals {
+                    if let Some(candidate) = expansion.expand_starting(production.body(), terminal)
+                        && better(&expansion.starting[head][terminal], &candidate)
+                    {
+                        expansion.starting[head][terminal] = Some(candidate);
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        expansion
+    }
+
+    fn expand_all(&self, seq: &[Symbol]) -> Option<Vec<usize>> {
+        let mut out = Vec::new();
+        for &symbol in seq {
+            match symbol {
+                Symbol::Terminal(terminal) => out.push(terminal),
+                Symbol::Nonterminal(nonterminal) => out.extend(self.shortest[nonterminal].as_ref()?),
+            }
+        }
+
+        Some(out)
+    }
+
+    // leading symbols which can vanish do, until one can start with `first`
+    fn expand_starting(&self, seq: &[Symbol], first: usize) -> Option<Vec<usize>> {
+        for (i, &symbol) in seq.iter().enumerate() {
+            let head = match symbol {
+                Symbol::Terminal(terminal) if terminal == first => Some(vec![terminal]),
+                Symbol::Terminal(_) => return None,
+                Symbol::Nonterminal(nonterminal) => self.starting[nonterminal][first].clone(),
+            };
+
+            if let Some(mut head) = head {
+                head.extend(self.expand_all(&seq[i + 1..])?);
+                return Some(head);
+            }
+
+            match symbol {
+                Symbol::Nonterminal(nonterminal) if self.shortest[nonterminal].as_ref()?.is_empty() => {}
+                _ => return None,
+            }
+        }
+
+        None
+    }
+
+    fn expand(&self, seq: &[Symbol], first: Option<usize>) -> Vec<usize> {
+        let expanded = match first {
+            // `$` ends input and is not derived
+            Some(first) if first != Grammar::END => self.expand_starting(seq, first),
+            _ => None,
+        };
+
+        expanded.or_else(|| self.expand_all(seq)).unwrap_or_else(|| {
+            // some nonterminal derives no terminal string; keep terminals only
+            seq.iter()
+                .filter_map(|&symbol| match symbol {
+                    Symbol::Terminal(terminal) => Some(terminal),
+                    Symbol::Nonterminal(_) => None,
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lex::Lexer;
+    use crate::parse::{Analysis, Automaton, Grammar, Parser};
+    use crate::pattern::rules;
+    use crate::store::Store;
+
+    fn conflicts(lex: &str, parse: &str) -> Vec<String> {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse(lex, &mut store).unwrap();
+
+        let rules = rules(parse, &mut store).unwrap();
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        let analysis = Analysis::new(&grammar);
+        let automaton = Automaton::new(&analysis);
+
+        let (_, conflicts) = Parser::new(&automaton);
+        conflicts
+            .iter()
+            .map(|conflict| conflict.error(&analysis, &automaton).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test() {
+        // dangling else is ambiguous
+        let errors = conflicts(
+            "Id : [a-z]+ ;",
+            r#"
+S : 'if' Id S | 'if' Id S 'else' S | Id ;
+            "#,
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("ambiguous example: 'if' Id 'if' Id Id • 'else' Id"));
+
+        // both sides are searched together, so that they derive same form
+        let errors = conflicts("Id : [a-z]+ ;", "E : E '+' E | Id ;");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains(
+            r#"  ambiguous example: Id '+' Id • '+' Id
+    shift derivation: E ::= [ E '+' E ::= [ E • '+' E ] ]
+    reduce derivation: E ::= [ E ::= [ E '+' E • ] '+' E ]"#
+        ));
+
+        // shortest derivation of shift alone would be `S ::= [ B ::= [ Id • 'x' ] ]`
+        let errors = conflicts("Id : [a-z]+ ;", "S : A 'x' 'y' | B | B 'y' ; A : Id ; B : Id 'x' ;");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].ends_with(
+            r#"  ambiguous example: Id • 'x' 'y'
+    shift derivation: S ::= [ B ::= [ Id • 'x' ] 'y' ]
+    reduce derivation: S ::= [ A ::= [ Id • ] 'x' 'y' ]"#
+        ));
+
+        // needs two tokens of lookahead, but is not ambiguous
+        let errors = conflicts(
+            "Id : [a-z]+ ;",
+            r#"
+S : A 'x' 'y' | B 'x' 'z' ;
+A : Id ;
+B : Id ;
+            "#,
+        );
+        assert_eq!(
+            errors,
+            [r#"(3,1): reduce/reduce conflict in state 1 on 'x':
+    (3,1): A -> Id .
+    (4,1): B -> Id .
+  reduce example: Id • 'x' 'y'
+    derivation: S ::= [ A ::= [ Id • ] 'x' 'y' ]
+  reduce example: Id • 'x' 'z'
+    derivation: S ::= [ B ::= [ Id • ] 'x' 'z' ]"#]
+        );
+    }
+}
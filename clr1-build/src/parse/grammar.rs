@@ -0,0 +1,306 @@
+use crate::error::{Error, ErrorKind};
+use crate::iter::Offset;
+use crate::lex::{Channel, Lexer};
+use crate::parse::lower::Lower;
+use crate::pattern::{Assoc, Kind, Rules};
+use crate::store::Store;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Symbol {
+    Terminal(usize),
+    Nonterminal(usize),
+}
+
+pub struct Terminal {
+    name: String,
+    // token kind emitted by lexer, `None` for end of input
+    kind: Option<usize>,
+}
+
+impl Terminal {
+    pub fn kind(&self) -> Option<usize> {
+        self.kind
+    }
+}
+
+pub struct Nonterminal {
+    name: String,
+    // rule this nonterminal is defined or synthesized from
+    at: Offset,
+    productions: Vec<usize>,
+    // synthesized from sub-pattern of rule
+    helper: bool,
+}
+
+impl Nonterminal {
+    pub fn helper(&self) -> bool {
+        self.helper
+    }
+
+    pub fn at(&self) -> Offset {
+        self.at
+    }
+
+    pub fn productions(&self) -> &[usize] {
+        &self.productions
+    }
+}
+
+pub struct Production {
+    head: usize,
+    body: Vec<Symbol>,
+    // index into `Grammar::captures` of `@name` each symbol of body is captured as
+    captures: Vec<Option<usize>>,
+    // [level, associativity] by `%prec` or of rightmost terminal which has one
+    precedence: Option<(usize, Assoc)>,
+}
+
+impl Production {
+    pub fn head(&self) -> usize {
+        self.head
+    }
+
+    pub fn body(&self) -> &[Symbol] {
+        &self.body
+    }
+
+    pub fn captures(&self) -> &[Option<usize>] {
+        &self.captures
+    }
+
+    pub fn precedence(&self) -> Option<(usize, Assoc)> {
+        self.precedence
+    }
+}
+
+pub struct Grammar {
+    terminals: Vec<Terminal>,
+    nonterminals: Vec<Nonterminal>,
+    productions: Vec<Production>,
+    // [level, associativity] by token kind or name in `%left`, `%right` and `%nonassoc`
+    precedences: HashMap<usize, (usize, Assoc)>,
+    // names in `@name`, without duplicates
+    captures: Vec<String>,
+    // terminal of `error`, if referred to
+    error: Option<usize>,
+}
+
+impl Grammar {
+    // terminal index of end of input
+    pub const END: usize = 0;
+    // nonterminal index of augmented start symbol
+    pub const ACCEPT: usize = 0;
+
+    // literals in parser rules are added to lexer as implicit tokens
+    pub fn new<'s>(
+        rules: &Rules,
+        lexer: &mut Lexer,
+        store: &mut Store<Cow<'s, str>>,
+    ) -> Result<Self, Error> {
+        if let Some(section) = rules.sections.first() {
+            return Err(ErrorKind::UnexpectedDirective.at(section.at));
+        }
+
+        let definitions = &rules.definitions;
+        let Some(start) = definitions.first() else {
+            return Err(ErrorKind::EmptyGrammar.at(Offset::new(0, 0)));
+        };
+
+        let mut grammar = Self {
+            terminals: vec![Terminal {
+                name: "$".to_string(),
+                kind: None,
+            }],
+            nonterminals: vec![Nonterminal {
+                name: "$accept".to_string(),
+                at: start.at,
+                productions: Vec::new(),
+                helper: false,
+            }],
+            productions: Vec::new(),
+            precedences: HashMap::new(),
+            captures: Vec::new(),
+            error: None,
+        };
+
+        let mut symbols = HashMap::new();
+        // skipped and hidden tokens never reach parser
+        for rule in lexer.rules().iter().filter(|rule| rule.channel() == Channel::Token) {
+            let terminal = grammar.terminal(store[rule.id()].to_string(), rule.id());
+            symbols.insert(rule.id(), Symbol::Terminal(terminal));
+        }
+
+        for def in definitions {
+            if def.kind != Kind::Token {
+                return Err(ErrorKind::LexerOnlyRule(def.kind).at(def.at));
+            }
+
+            let nonterminal = grammar.nonterminals.len();
+            let lexical = lexer.channel(def.name).is_some() || lexer.is_fragment(def.name);
+            if symbols.insert(def.name, Symbol::Nonterminal(nonterminal)).is_some() || lexical {
+                return Err(ErrorKind::DuplicateRule(store[def.name].to_string()).at(def.at));
+            }
+
+            grammar.nonterminal(store[def.name].to_string(), def.at);
+        }
+
+        grammar.add(Self::ACCEPT, vec![(Symbol::Nonterminal(Self::ACCEPT + 1), None)], None);
+
+        let mut lower = Lower::new(&mut grammar, &symbols, lexer, store);
+        lower.precedences(&rules.precedences)?;
+        for (i, def) in definitions.iter().enumerate() {
+            lower.rule(Self::ACCEPT + 1 + i, &def.pat, def.at)?;
+        }
+
+        Ok(grammar)
+    }
+
+    pub fn terminal(&mut self, name: String, kind: usize) -> usize {
+        self.terminals.push(Terminal {
+            name,
+            kind: Some(kind),
+        });
+        self.terminals.len() - 1
+    }
+
+    // `error` pseudo-terminal, added on first reference; lexer gives tokens of its kind for unmatched input
+    pub fn error(&mut self, kind: usize) -> usize {
+        match self.error {
+            Some(terminal) => terminal,
+            None => {
+                let terminal = self.terminal("error".to_string(), kind);
+                self.error = Some(terminal);
+                terminal
+            }
+        }
+    }
+
+    pub fn error_terminal(&self) -> Option<usize> {
+        self.error
+    }
+
+    pub fn nonterminal(&mut self, name: String, at: Offset) -> usize {
+        self.nonterminals.push(Nonterminal {
+            name,
+            at,
+            productions: Vec::new(),
+            helper: false,
+        });
+        self.nonterminals.len() - 1
+    }
+
+    pub fn helper(&mut self, name: String, at: Offset) -> usize {
+        let nonterminal = self.nonterminal(name, at);
+        self.nonterminals[nonterminal].helper = true;
+        nonterminal
+    }
+
+    // body with capture of each symbol
+    pub fn add(&mut self, head: usize, body: Vec<(Symbol, Option<usize>)>, precedence: Option<(usize, Assoc)>) {
+        let (body, captures): (Vec<_>, Vec<_>) = body.into_iter().unzip();
+        let precedence = precedence.or_else(|| {
+            body.iter().rev().find_map(|&symbol| match symbol {
+                Symbol::Terminal(terminal) => self.precedence(terminal),
+                Symbol::Nonterminal(_) => None,
+            })
+        });
+
+        self.nonterminals[head].productions.push(self.productions.len());
+        self.productions.push(Production {
+            head,
+            body,
+            captures,
+            precedence,
+        });
+    }
+
+    // index of capture name, added if new
+    pub fn capture(&mut self, name: String) -> usize {
+        match self.captures.iter().position(|capture| *capture == name) {
+            Some(i) => i,
+            None => {
+                self.captures.push(name);
+                self.captures.len() - 1
+            }
+        }
+    }
+
+    pub fn captures(&self) -> &[String] {
+        &self.captures
+    }
+
+    // false if already declared
+    pub fn declare(&mut self, key: usize, level: usize, assoc: Assoc) -> bool {
+        self.precedences.insert(key, (level, assoc)).is_none()
+    }
+
+    pub fn declared(&self, key: usize) -> Option<(usize, Assoc)> {
+        self.precedences.get(&key).copied()
+    }
+
+    pub fn precedence(&self, terminal: usize) -> Option<(usize, Assoc)> {
+        self.declared(self.terminals[terminal].kind?)
+    }
+
+    pub fn terminals(&self) -> &[Terminal] {
+        &self.terminals
+    }
+
+    pub fn nonterminals(&self) -> &[Nonterminal] {
+        &self.nonterminals
+    }
+
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    // `A -> B . c`
+    pub fn dotted(&self, production: usize, dot: usize) -> String {
+        let production = &self.productions[production];
+
+        let mut str = format!("{} ->", self.nonterminals[production.head].name);
+        for (i, &symbol) in production.body.iter().enumerate() {
+            if i == dot {
+                str.push_str(" .");
+            }
+            str.push(' ');
+            str.push_str(self.name(symbol));
+        }
+        if dot == production.body.len() {
+            str.push_str(" .");
+        }
+
+        str
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        match symbol {
+            Symbol::Terminal(i) => &self.terminals[i].name,
+            Symbol::Nonterminal(i) => &self.nonterminals[i].name,
+        }
+    }
+}
+
+impl Display for Grammar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for nonterminal in &self.nonterminals {
+            writeln!(f, "{} {}", nonterminal.at, nonterminal.name)?;
+            for &production in &nonterminal.productions {
+                write!(f, "    {:>4}:", production)?;
+                let production = &self.productions[production];
+                for (&symbol, capture) in production.body.iter().zip(&production.captures) {
+                    write!(f, " {}", self.name(symbol))?;
+                    if let Some(capture) = capture {
+                        write!(f, "@{}", self.captures[*capture])?;
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
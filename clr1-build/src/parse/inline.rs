@@ -0,0 +1,255 @@
+use crate::error::{Error, ErrorKind};
+use crate::parse::ast::{signature, Ast, Count, Field, Params, Target};
+use crate::parse::grammar::Grammar;
+use crate::parse::lower::{alternatives, code};
+use crate::pattern::{is_ident, rust_literal, Rules};
+use crate::store::Store;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+// code blocks of alternatives, run by `parse` of generated code as productions are reduced.
+// it implements `Actions` with `__Value`, enum of what each rule evaluates to.
+pub struct Inline<'a> {
+    ast: &'a Ast,
+    // of each rule; `()` unless declared by `%type`
+    types: Vec<String>,
+    // of each production of rule, with captures bound; `()` if it has none
+    codes: HashMap<usize, String>,
+}
+
+impl<'a> Inline<'a> {
+    // `None` if grammar has neither code nor `%type`
+    pub fn new(rules: &Rules, grammar: &Grammar, ast: &'a Ast, store: &Store<Cow<str>>) -> Result<Option<Self>, Error> {
+        let indices = rules.definitions.iter().enumerate().map(|(i, def)| (def.name, i)).collect::<HashMap<_, _>>();
+
+        let mut types = vec![None; rules.definitions.len()];
+        for ty in &rules.types {
+            for name in &ty.names {
+                let Some(&rule) = indices.get(name) else {
+                    return Err(ErrorKind::UndefinedRule(store[*name].to_string()).at(ty.at));
+                };
+                if types[rule].replace(ty.ty.clone()).is_some() {
+                    return Err(ErrorKind::DuplicateType(store[*name].to_string()).at(ty.at));
+                }
+            }
+        }
+
+        let methods = ast.actions();
+        let methods = methods.methods();
+        let mut codes = HashMap::new();
+        for (i, def) in rules.definitions.iter().enumerate() {
+            let productions = grammar.nonterminals()[Grammar::ACCEPT + 1 + i].productions();
+            for (alternative, &production) in alternatives(&def.pat).into_iter().zip(productions) {
+                match code(alternative).1 {
+                    Some(code) => {
+                        let fields = match methods.iter().find(|method| method.2 == production) {
+                            Some((_, _, _, Params::Fields(fields))) => fields,
+                            _ => &[][..],
+                        };
+                        codes.insert(production, bind(code, fields).map_err(|kind| kind.at(def.at))?);
+                    }
+                    None if types[i].is_some() => {
+                        return Err(ErrorKind::MissingCode(store[def.name].to_string()).at(def.at));
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if codes.is_empty() && rules.types.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            ast,
+            types: types.into_iter().map(|ty| ty.unwrap_or_else(|| "()".to_string())).collect(),
+            codes,
+        }))
+    }
+}
+
+// `$name` and `@name` of captures in code become their variables; unknown `$name` is an error
+fn bind(code: &str, fields: &[Field]) -> Result<String, ErrorKind> {
+    let mut bound = String::new();
+    let mut i = 0;
+    while let Some(ch) = code[i..].chars().next() {
+        if let Some(len) = rust_literal(&code[i..]) {
+            bound.push_str(&code[i..i + len]);
+            i += len;
+            continue;
+        }
+        i += ch.len_utf8();
+
+        if ch == '$' || ch == '@' {
+            let len = code[i..].find(|ch: char| !is_ident(ch)).unwrap_or(code.len() - i);
+            let name = &code[i..i + len];
+            match fields.iter().find(|field| field.capture == name) {
+                Some(field) => {
+                    bound.push_str(&field.name);
+                    i += len;
+                    continue;
+                }
+                None if ch == '$' && len > 0 => return Err(ErrorKind::UndefinedCapture(name.to_string())),
+                None => {}
+            }
+        }
+        bound.push(ch);
+    }
+
+    Ok(bound)
+}
+
+// `__Value` of rule, from `__Value`
+fn unwrap(field: &Field, rule: &str) -> String {
+    match field.count {
+        Count::One => format!("{}.into_{}()", field.name, rule),
+        Count::Optional => format!("{}.map(__Value::into_{})", field.name, rule),
+        Count::Many => format!("{}.into_iter().map(__Value::into_{}).collect::<Vec<_>>()", field.name, rule),
+    }
+}
+
+// at indent of `generated` module, after `Actions`
+impl Display for Inline<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rules = (0..self.types.len()).map(|rule| self.ast.ident(rule)).collect::<Vec<_>>();
+
+        writeln!(f, "    // what rule evaluates to, by its `%type`")?;
+        writeln!(f, "    enum __Value<'a> {{")?;
+        for (rule, ty) in rules.iter().zip(&self.types) {
+            writeln!(f, "        {}({}),", rule, ty)?;
+        }
+        writeln!(f, "        __Lifetime(std::marker::PhantomData<&'a ()>),")?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    #[allow(non_snake_case)]")?;
+        writeln!(f, "    impl<'a> __Value<'a> {{")?;
+        for (i, (rule, ty)) in rules.iter().zip(&self.types).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "        fn into_{}(self) -> {} {{", rule, ty)?;
+            writeln!(f, "            match self {{")?;
+            writeln!(f, "                Self::{}(value) => value,", rule)?;
+            writeln!(f, "                _ => unreachable!(\"value is not of {}\"),", rule)?;
+            writeln!(f, "            }}")?;
+            writeln!(f, "        }}")?;
+        }
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    struct __Inline;")?;
+        writeln!(f)?;
+        writeln!(f, "    #[allow(unused_braces, unused_variables)]")?;
+        writeln!(f, "    impl<'a> Actions<'a> for __Inline {{")?;
+        writeln!(f, "        type Value = __Value<'a>;")?;
+        let actions = self.ast.actions();
+        for (rule, name, production, params) in actions.methods() {
+            writeln!(f)?;
+            writeln!(f, "        fn {}(&mut self{}) -> Self::Value {{", name, signature(&params))?;
+            let fields = match params {
+                Params::Fields(fields) => fields,
+                Params::Single(_) => &[][..],
+            };
+            for field in fields {
+                if let Target::Rule(rule) = field.target {
+                    writeln!(f, "            let {} = {};", field.name, unwrap(field, self.ast.ident(rule)))?;
+                }
+            }
+            match self.codes.get(&production) {
+                Some(code) => writeln!(f, "            __Value::{}({{ {} }})", rules[rule], code)?,
+                None => writeln!(f, "            __Value::{}(())", rules[rule])?,
+            }
+            writeln!(f, "        }}")?;
+        }
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    // runs code of alternatives as they are reduced; returns what start rule evaluates to")?;
+        writeln!(f, "    pub fn parse<'a>(")?;
+        writeln!(f, "        tokens: impl IntoIterator<Item = Token<'a, TokenKind>>,")?;
+        writeln!(f, "    ) -> Result<{}, Error<TokenKind>> {{", self.types[0])?;
+        writeln!(f, "        __Inline.parse(tokens).map(__Value::into_{})", rules[0])?;
+        writeln!(f, "    }}")?;
+        writeln!(f)?;
+
+        writeln!(f, "    // `parse`, recovering from errors by `error` of grammar; value is `None` if it could not")?;
+        writeln!(f, "    pub fn parse_recover<'a>(")?;
+        writeln!(f, "        tokens: impl IntoIterator<Item = Token<'a, TokenKind>>,")?;
+        writeln!(f, "    ) -> (Option<{}>, Vec<Error<TokenKind>>) {{", self.types[0])?;
+        writeln!(f, "        let (value, errors) = __Inline.parse_recover(tokens);")?;
+        writeln!(f, "        (value.map(__Value::into_{}), errors)", rules[0])?;
+        write!(f, "    }}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::pattern::rules;
+
+    #[test]
+    fn test() {
+        let generate = |parse| {
+            let mut store = Store::new();
+            let mut lexer = Lexer::parse("Id : [a-z]+ ; Num : [0-9]+ ;", &mut store).unwrap();
+            let rules = rules(parse, &mut store)?;
+            let grammar = Grammar::new(&rules, &mut lexer, &mut store)?;
+            let ast = Ast::new(&rules, &grammar, &store)?;
+            Inline::new(&rules, &grammar, &ast, &store).map(|inline| inline.map(|inline| inline.to_string()))
+        };
+
+        let inline = generate(
+            r#"
+%left '+' ;
+%type <Vec<i64>> List ;
+%type <i64> Expr ;
+List : Expr@items* { $items } ;
+Expr : Expr@a '+' Expr@b { @a + $b }
+     | Num@n { $n.value().parse::<i64>().unwrap() /* $x '}' */ }
+     | Id { let s = "$x}"; 0 }
+     | '(' ')' %prec '+' { { 0 } } ;
+Unit : 'u' ;
+            "#,
+        );
+        let inline = inline.unwrap().unwrap();
+        assert!(inline.contains("List(Vec<i64>),\n        Expr(i64),\n        Unit(()),"));
+        assert!(inline.contains("let items = items.into_iter().map(__Value::into_Expr).collect::<Vec<_>>();"));
+        assert!(inline.contains("__Value::Expr({ a + b })"));
+        assert!(inline.contains("__Value::Expr({ n.value().parse::<i64>().unwrap() /* $x '}' */ })"));
+        assert!(inline.contains("__Value::Expr({ let s = \"$x}\"; 0 })"));
+        assert!(inline.contains("__Value::Expr({ { 0 } })"));
+        assert!(inline.contains("fn reduce_Unit(&mut self) -> Self::Value {\n            __Value::Unit(())"));
+        assert!(inline.contains(") -> Result<Vec<i64>, Error<TokenKind>> {"));
+
+        // braces and `$name` in raw strings, byte strings and byte chars are not code
+        let code = r###"{ r#"}"$x"#.len() + br##"{"#"##.len() + b'}' as usize }"###;
+        let parse = r###"%type <usize> S ; S : Id { r#"}"$x"#.len() + br##"{"#"##.len() + b'}' as usize } ;"###;
+        let inline = generate(parse);
+        assert!(inline.unwrap().unwrap().contains(&format!("__Value::S({})", code)));
+        let inline = generate(r#"%type <usize> S ; S : Id { r"}".len() + b"}\"{".len() + b'\'' as usize } ;"#);
+        assert!(inline.unwrap().unwrap().contains(r#"__Value::S({ r"}".len() + b"}\"{".len() + b'\'' as usize })"#));
+
+        // quantifier, not code
+        assert!(generate("S : Id{2} ;").unwrap().is_none());
+
+        let error = |parse| generate(parse).err().unwrap().to_string();
+        let missing = error("%type <i64> S ; S : Id | Num { 0 } ;");
+        assert!(missing.ends_with("alternative of rule 'S' has no code, while rule has type"));
+        assert!(error("S : Id@x { $y } ;").ends_with("capture 'y' is not defined in alternative"));
+        let duplicate = error("%type <i64> S ; %type <u8> S ; S : Id { 0 } ;");
+        assert!(duplicate.ends_with("type of rule 'S' is already declared"));
+        assert!(error("%type <i64> T ; S : Id ;").ends_with("rule 'T' is not defined"));
+        let misplaced = error("S : Id { 0 } Num ;");
+        assert!(misplaced.ends_with("code block is only allowed at end of top-level alternative of parser rule"));
+        assert!(error("S : Id { 0 ;").ends_with("code block not closed"));
+
+        let mut store = Store::new();
+        let error = Lexer::parse("A : 'a' { 0 } ;", &mut store).err().unwrap().to_string();
+        assert!(error.ends_with("directive is not allowed here"));
+        let error = Lexer::parse("%type <i64> A ; A : 'a' ;", &mut store).err().unwrap().to_string();
+        assert!(error.ends_with("directive is not allowed here"));
+    }
+}
@@ -0,0 +1,344 @@
+use crate::error::{Error, ErrorKind};
+use crate::iter::Offset;
+use crate::lex::Lexer;
+use crate::parse::grammar::{Grammar, Symbol};
+use crate::pattern::{Assoc, Pattern, Precedence, Quantifier};
+use crate::store::Store;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+// rewrites EBNF pattern of parser rule into BNF productions.
+// sub-patterns which cannot be inlined are lowered into helper nonterminals:
+//
+//   X?        H : X | ;
+//   X*        H : H X | ;
+//   X+        H : H X | X ;
+//   X{2,}     X H            (H is X+)
+//   X{1,3}    H : X | X X | X X X ;
+//   (A | B)   H : A | B ;
+pub struct Lower<'a, 's> {
+    grammar: &'a mut Grammar,
+    symbols: &'a HashMap<usize, Symbol>,
+    lexer: &'a mut Lexer,
+    store: &'a mut Store<Cow<'s, str>>,
+    literals: HashMap<String, usize>,
+    // rule being lowered and number of helpers synthesized for it
+    rule: usize,
+    helpers: usize,
+}
+
+impl<'a, 's> Lower<'a, 's> {
+    pub fn new(
+        grammar: &'a mut Grammar,
+        symbols: &'a HashMap<usize, Symbol>,
+        lexer: &'a mut Lexer,
+        store: &'a mut Store<Cow<'s, str>>,
+    ) -> Self {
+        Self {
+            grammar,
+            symbols,
+            lexer,
+            store,
+            literals: HashMap::new(),
+            rule: 0,
+            helpers: 0,
+        }
+    }
+
+    // declared before rules are lowered, so that productions pick up precedence of their terminals
+    pub fn precedences(&mut self, precedences: &[Precedence]) -> Result<(), Error> {
+        for (level, precedence) in precedences.iter().enumerate() {
+            for symbol in &precedence.symbols {
+                let key = self.key(symbol);
+                if !self.grammar.declare(key, level, precedence.assoc) {
+                    let name = self.store[key].to_string();
+                    return Err(ErrorKind::DuplicatePrecedence(name).at(precedence.at));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // token kind of literal or name of reference, which need not be defined in `%prec`
+    fn key(&mut self, symbol: &Pattern) -> usize {
+        match symbol {
+            Pattern::Literal(str) => match self.lexer.literal(str) {
+                Some(id) => id,
+                None => self.store.add(format!("'{}'", str.escape_default())),
+            },
+            Pattern::Reference(id, _) => *id,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn rule(&mut self, head: usize, pat: &Pattern, at: Offset) -> Result<(), Error> {
+        self.rule = head;
+        self.helpers = 0;
+
+        self.productions(head, pat).map_err(|kind| kind.at(at))
+    }
+
+    fn productions(&mut self, head: usize, pat: &Pattern) -> Result<(), ErrorKind> {
+        for alternative in alternatives(pat) {
+            // code only ends alternatives of rule itself, not of group lowered into helper
+            let (alternative, code) = code(alternative);
+            if code.is_some() && head != self.rule {
+                return Err(ErrorKind::UnexpectedCode);
+            }
+            let (alternative, precedence) = match alternative {
+                [rest @ .., Pattern::Prec(symbol)] => (rest, Some(self.prec(symbol)?)),
+                list => (list, None),
+            };
+
+            let mut body = Vec::new();
+            for pat in alternative {
+                self.sequence(pat, &mut body)?;
+            }
+            self.grammar.add(head, body, precedence);
+        }
+
+        Ok(())
+    }
+
+    fn prec(&mut self, symbol: &Pattern) -> Result<(usize, Assoc), ErrorKind> {
+        let key = self.key(symbol);
+        self.grammar
+            .declared(key)
+            .ok_or_else(|| ErrorKind::UndefinedPrecedence(self.store[key].to_string()))
+    }
+
+    // at group or quantifier it is synthesized from
+    fn helper(&mut self, at: Offset) -> usize {
+        self.helpers += 1;
+
+        let name = format!("{}#{}", self.grammar.name(Symbol::Nonterminal(self.rule)), self.helpers);
+        self.grammar.helper(name, at)
+    }
+
+    // symbols with their captures
+    fn sequence(&mut self, pat: &Pattern, body: &mut Vec<(Symbol, Option<usize>)>) -> Result<(), ErrorKind> {
+        match pat {
+            Pattern::Reference(id, capture) => {
+                let symbol = match self.symbols.get(id) {
+                    Some(&symbol) => symbol,
+                    // unless some rule is named so
+                    None if self.store[*id] == "error" => Symbol::Terminal(self.grammar.error(self.lexer.error())),
+                    None => {
+                        let name = self.store[*id].to_string();
+                        return Err(match (self.lexer.is_fragment(*id), self.lexer.channel(*id)) {
+                            (true, _) => ErrorKind::FragmentReference(name),
+                            (_, Some(_)) => ErrorKind::TriviaReference(name),
+                            _ => ErrorKind::UndefinedRule(name),
+                        });
+                    }
+                };
+
+                let capture = capture.map(|name| self.grammar.capture(self.store[name].to_string()));
+                body.push((symbol, capture));
+            }
+            Pattern::Literal(str) => {
+                let symbol = match self.literals.get(str) {
+                    Some(&terminal) => Symbol::Terminal(terminal),
+                    // lexer rule which is exactly this literal
+                    None => match self.lexer.literal(str) {
+                        Some(id) => self.symbols[&id],
+                        None => {
+                            let name = format!("'{}'", str.escape_default());
+                            let kind = self.store.add(name.clone());
+
+                            self.lexer.implicit(kind, str.clone());
+
+                            let terminal = self.grammar.terminal(name, kind);
+                            self.literals.insert(str.clone(), terminal);
+                            Symbol::Terminal(terminal)
+                        }
+                    },
+                };
+
+                body.push((symbol, None));
+            }
+            Pattern::Group(list) => {
+                for pat in list {
+                    self.sequence(pat, body)?;
+                }
+            }
+            Pattern::Or(_, at) => {
+                let helper = self.helper(*at);
+                self.productions(helper, pat)?;
+                body.push((Symbol::Nonterminal(helper), None));
+            }
+            Pattern::Quantifier(quantifier) => {
+                self.quantifier(quantifier, body)?;
+            }
+            Pattern::Class(_) => {
+                return Err(ErrorKind::UnsupportedPattern);
+            }
+            Pattern::Prec(_) | Pattern::Mode(_) => {
+                return Err(ErrorKind::UnexpectedDirective);
+            }
+            Pattern::Code(_) => {
+                return Err(ErrorKind::UnexpectedCode);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn quantifier(
+        &mut self,
+        quantifier: &Quantifier,
+        body: &mut Vec<(Symbol, Option<usize>)>,
+    ) -> Result<(), ErrorKind> {
+        let (min, max) = (*quantifier.range().start(), *quantifier.range().end());
+        if min > max {
+            return Err(ErrorKind::MalformedQuantifier);
+        }
+
+        // lowered once, so that nested helpers are not duplicated per repetition
+        let mut item = Vec::new();
+        self.sequence(quantifier.what(), &mut item)?;
+
+        let repeat = |n: usize| item.iter().copied().cycle().take(item.len() * n);
+
+        if max == usize::MAX {
+            // X{min,} = X{min-1} X+
+            let helper = self.helper(quantifier.at());
+            let recursive = [(Symbol::Nonterminal(helper), None)].into_iter().chain(item.iter().copied());
+            self.grammar.add(helper, recursive.collect(), None);
+
+            if min == 0 {
+                self.grammar.add(helper, Vec::new(), None);
+            } else {
+                body.extend(repeat(min - 1));
+                self.grammar.add(helper, item.clone(), None);
+            }
+
+            body.push((Symbol::Nonterminal(helper), None));
+        } else if min == max {
+            body.extend(repeat(min));
+        } else {
+            let helper = self.helper(quantifier.at());
+            for n in min..=max {
+                self.grammar.add(helper, repeat(n).collect(), None);
+            }
+
+            body.push((Symbol::Nonterminal(helper), None));
+        }
+
+        Ok(())
+    }
+}
+
+// top-level `|` of rule separates productions
+pub fn alternatives(pat: &Pattern) -> Vec<&Pattern> {
+    match pat {
+        Pattern::Group(list) => match list.as_slice() {
+            [Pattern::Or(alternatives, _)] => alternatives.iter().collect(),
+            _ => vec![pat],
+        },
+        Pattern::Or(alternatives, _) => alternatives.iter().collect(),
+        _ => vec![pat],
+    }
+}
+
+// symbols of alternative, and code at its end
+pub fn code(alternative: &Pattern) -> (&[Pattern], Option<&str>) {
+    let list = match alternative {
+        Pattern::Group(list) => list.as_slice(),
+        pat => std::slice::from_ref(pat),
+    };
+
+    match list {
+        [rest @ .., Pattern::Code(code)] => (rest, Some(code)),
+        list => (list, None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lex::Lexer;
+    use crate::parse::{Analysis, Grammar};
+    use crate::pattern::rules;
+    use crate::store::Store;
+
+    #[test]
+    fn test() {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse("Id : [a-z]+ ; Num : [0-9]+ ;", &mut store).unwrap();
+
+        let rules = rules(
+            r#"
+Call : Id '(' (Arg (',' Arg)*)? ')' ;
+Arg  : (Id | Num)+ '!'{2,} '?'{1,3} ;
+            "#,
+            &mut store,
+        )
+        .unwrap();
+
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+
+        // helper is at group or quantifier it is synthesized from
+        let expected = r#"(2,1) $accept
+       0: Call
+(2,1) Call
+       5: Id '(' Call#2 ')'
+(3,1) Arg
+      15: Arg#2 '!' Arg#3 Arg#4
+(2,29) Call#1
+       1: Call#1 ',' Arg
+       2:
+(2,31) Call#2
+       3:
+       4: Arg Call#1
+(3,8) Arg#1
+       6: Id
+       7: Num
+(3,18) Arg#2
+       8: Arg#2 Arg#1
+       9: Arg#1
+(3,23) Arg#3
+      10: Arg#3 '!'
+      11: '!'
+(3,31) Arg#4
+      12: '?'
+      13: '?' '?'
+      14: '?' '?' '?'
+"#;
+        assert_eq!(grammar.to_string(), expected);
+
+        let analysis = Analysis::new(&grammar).to_string();
+        assert!(analysis.contains("Call#1\n    nullable: true"));
+        assert!(analysis.contains("Call#2\n    nullable: true"));
+        assert!(analysis.contains("Arg#2\n    nullable: false"));
+
+        let error = |parse| {
+            let mut store = Store::new();
+            let mut lexer = Lexer::parse("fragment Digit : [0-9] ; Num : Digit+ ; skip WS : ' '+ ;", &mut store).unwrap();
+            let rules = crate::pattern::rules(parse, &mut store).unwrap();
+            Grammar::new(&rules, &mut lexer, &mut store).err().unwrap().to_string()
+        };
+        assert!(error("S : Num Digit ;").ends_with("rule 'Digit' is a fragment, which cannot be used in parser rule"));
+        assert!(error("S : Num ; Digit : Num ;").ends_with("rule 'Digit' is already defined"));
+        assert!(error("S : Num WS ;").ends_with("rule 'WS' is skipped or hidden, which cannot be used in parser rule"));
+        assert!(error("%mode M ; S : Num ;").ends_with("directive is not allowed here"));
+        assert!(error("S : Num %pop ;").ends_with("directive is not allowed here"));
+        let code = "code block is only allowed at end of top-level alternative of parser rule";
+        assert!(error("S : (Num { 1 } | Num Num { 2 }) ;").ends_with(code));
+        assert!(error("S : (Num | Num Num { 2 })* ;").ends_with(code));
+        assert!(error("S : Num { 1 } Num ;").ends_with(code));
+        assert!(error("fragment S : Num ;").ends_with("fragment rule is only allowed in lexer"));
+
+        // `error` is terminal of its own, unless some rule is named so
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse("Num : [0-9]+ ;", &mut store).unwrap();
+        let rules = crate::pattern::rules("S : Num ';' | error ';' ;", &mut store).unwrap();
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        assert!(grammar.to_string().contains("error ';'"));
+        assert!(grammar.error_terminal().is_some());
+
+        let rules = crate::pattern::rules("S : error ; error : Num ;", &mut store).unwrap();
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        assert!(grammar.error_terminal().is_none());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::parse::automaton::{Automaton, State};
+use crate::parse::grammar::Grammar;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// partitions of canonical LR(1) states, as block index per state. state 0 always stays in block 0.
+//
+//   LALR(1)   states of same core are merged
+//   minimal   Pager's minimal LR(1): states of same core are merged if they are weakly compatible, and so are
+//             their successors. merging states of same core cannot add shift/reduce conflict, and weak
+//             compatibility keeps it from adding reduce/reduce conflict, both in merged states and in states
+//             their lookaheads flow into; so its tables have no conflict canonical LR(1) does not have,
+//             and as many states as LALR(1) if LALR(1) adds no conflict. as LALR(1), it may detect error
+//             after more reductions than canonical LR(1)
+
+pub fn lalr(automaton: &Automaton) -> Vec<usize> {
+    let mut cores = HashMap::new();
+    automaton
+        .states()
+        .iter()
+        .map(|state| {
+            let next = cores.len();
+            *cores.entry(core(state)).or_insert(next)
+        })
+        .collect()
+}
+
+pub fn minimal(automaton: &Automaton) -> Vec<usize> {
+    let mut sets = Sets::new(automaton);
+
+    // each state is merged into first earlier group it is compatible with, as in Pager's construction;
+    // states already merged as successors of others are left as they are
+    let mut groups: HashMap<BTreeSet<(usize, usize)>, Vec<usize>> = HashMap::new();
+    for (i, state) in automaton.states().iter().enumerate() {
+        if sets.find(i) != i {
+            continue;
+        }
+
+        let roots = groups.entry(core(state)).or_default();
+        roots.retain(|&root| sets.find(root) == root);
+        if !roots.iter().any(|&root| sets.unify(root, i)) {
+            roots.push(i);
+        }
+    }
+
+    sets.blocks()
+}
+
+// number of reduce/reduce conflicts in merged states which no state merged into them has
+pub fn added_conflicts(automaton: &Automaton, blocks: &[usize]) -> usize {
+    let grammar = automaton.grammar();
+
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (state, &block) in blocks.iter().enumerate() {
+        members.entry(block).or_default().push(state);
+    }
+
+    members
+        .values()
+        .map(|states| {
+            let mut merged = Reduces::default();
+            for &state in states {
+                merged.add(grammar, &automaton.states()[state]);
+            }
+            merged.added().len()
+        })
+        .sum()
+}
+
+fn core(state: &State) -> BTreeSet<(usize, usize)> {
+    state.items().iter().map(|item| (item.production(), item.dot())).collect()
+}
+
+// lookaheads by core of kernel item
+type Kernel = BTreeMap<(usize, usize), BTreeSet<usize>>;
+
+// Pager's weak compatibility: merging does not bring two kernel items a lookahead in common which they had in
+// neither state, as reduce/reduce conflict could only come from there
+fn compatible(a: &Kernel, b: &Kernel) -> bool {
+    let a = a.values().collect::<Vec<_>>();
+    let b = b.values().collect::<Vec<_>>();
+    let meets = |x: &BTreeSet<usize>, y: &BTreeSet<usize>| !x.is_disjoint(y);
+
+    (0..a.len()).all(|i| {
+        (i + 1..a.len()).all(|j| {
+            !(meets(a[i], b[j]) || meets(b[i], a[j])) || meets(a[i], a[j]) || meets(b[i], b[j])
+        })
+    })
+}
+
+// groups of states merged so far, as union-find, with kernel lookaheads of each group at its root.
+// every member of a group has its successor on each symbol in same group
+struct Sets<'a, 'g> {
+    automaton: &'a Automaton<'g>,
+    parents: Vec<usize>,
+    kernels: Vec<Kernel>,
+}
+
+impl<'a, 'g> Sets<'a, 'g> {
+    fn new(automaton: &'a Automaton<'g>) -> Self {
+        let kernels = automaton
+            .states()
+            .iter()
+            .map(|state| {
+                let mut kernel = Kernel::new();
+                for item in state.items() {
+                    // augmented start item is only kernel item with dot at start
+                    if item.dot() > 0 || item.production() == 0 {
+                        kernel.entry((item.production(), item.dot())).or_default().insert(item.lookahead());
+                    }
+                }
+                kernel
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            automaton,
+            parents: (0..kernels.len()).collect(),
+            kernels,
+        }
+    }
+
+    fn find(&self, mut state: usize) -> usize {
+        while self.parents[state] != state {
+            state = self.parents[state];
+        }
+        state
+    }
+
+    // merges groups of `a` and `b`, then groups of their successors on each symbol, and so on;
+    // if some pair is not weakly compatible, undoes all of it and returns false
+    fn unify(&mut self, a: usize, b: usize) -> bool {
+        let states = self.automaton.states();
+
+        // root which stopped being one, and kernel its new root had before
+        let mut undo = Vec::new();
+        let mut pending = vec![(a, b)];
+        while let Some((a, b)) = pending.pop() {
+            let (a, b) = (self.find(a), self.find(b));
+            if a == b {
+                continue;
+            }
+
+            if !compatible(&self.kernels[a], &self.kernels[b]) {
+                for (b, kernel) in undo.into_iter().rev() {
+                    let a = self.parents[b];
+                    self.parents[b] = b;
+                    self.kernels[a] = kernel;
+                }
+                return false;
+            }
+
+            let mut kernel = self.kernels[a].clone();
+            for (core, lookaheads) in &self.kernels[b] {
+                kernel.entry(*core).or_default().extend(lookaheads);
+            }
+            undo.push((b, std::mem::replace(&mut self.kernels[a], kernel)));
+            self.parents[b] = a;
+
+            for (symbol, &to) in states[a].transitions() {
+                pending.push((to, states[b].transitions()[symbol]));
+            }
+        }
+
+        true
+    }
+
+    // groups numbered in order of their first state
+    fn blocks(&self) -> Vec<usize> {
+        let mut numbers = HashMap::new();
+        (0..self.parents.len())
+            .map(|state| {
+                let next = numbers.len();
+                *numbers.entry(self.find(state)).or_insert(next)
+            })
+            .collect()
+    }
+}
+
+// reductions by lookahead of states merged so far
+#[derive(Default, Clone)]
+struct Reduces {
+    merged: BTreeMap<usize, BTreeSet<usize>>,
+    // lookaheads on which some merged state alone has reduce/reduce conflict
+    conflicting: BTreeSet<usize>,
+}
+
+impl Reduces {
+    fn add(&mut self, grammar: &Grammar, state: &State) {
+        let mut reduces: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for item in state.items() {
+            if item.next(grammar).is_none() {
+                reduces.entry(item.lookahead()).or_default().insert(item.production());
+            }
+        }
+
+        for (lookahead, productions) in reduces {
+            if productions.len() > 1 {
+                self.conflicting.insert(lookahead);
+            }
+            self.merged.entry(lookahead).or_default().extend(productions);
+        }
+    }
+
+    // lookaheads on which merging brought reduce/reduce conflict
+    fn added(&self) -> Vec<usize> {
+        self.merged
+            .iter()
+            .filter(|(lookahead, productions)| productions.len() > 1 && !self.conflicting.contains(lookahead))
+            .map(|(&lookahead, _)| lookahead)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::parse::{Analysis, Parser};
+    use crate::pattern::rules;
+    use crate::store::Store;
+
+    fn check(lex: &str, parse: &str, f: impl FnOnce(&Automaton)) {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse(lex, &mut store).unwrap();
+
+        let rules = rules(parse, &mut store).unwrap();
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        f(&Automaton::new(&Analysis::new(&grammar)));
+    }
+
+    #[test]
+    fn test() {
+        // dragon book 4.54; LALR(1) merges 3 pairs without conflict
+        check("c : 'c' ; d : 'd' ;", "S : C C ; C : c C | d ;", |automaton| {
+            assert_eq!(automaton.states().len(), 10);
+
+            let blocks = lalr(automaton);
+            assert_eq!(added_conflicts(automaton, &blocks), 0);
+            assert_eq!(minimal(automaton), blocks);
+
+            let merged = automaton.merge(&blocks);
+            assert_eq!(merged.states().len(), 7);
+            assert!(Parser::new(&merged).1.is_empty());
+        });
+
+        // dragon book 4.58; LR(1) but not LALR(1)
+        check(
+            "a : 'a' ; b : 'b' ; c : 'c' ; d : 'd' ; e : 'e' ;",
+            "S : a A d | b B d | a B e | b A e ; A : c ; B : c ;",
+            |automaton| {
+                assert!(Parser::new(automaton).1.is_empty());
+
+                let blocks = lalr(automaton);
+                assert_eq!(added_conflicts(automaton, &blocks), 2);
+                assert_eq!(Parser::new(&automaton.merge(&blocks)).1.len(), 2);
+
+                let blocks = minimal(automaton);
+                assert_eq!(added_conflicts(automaton, &blocks), 0);
+
+                let merged = automaton.merge(&blocks);
+                assert!(Parser::new(&merged).1.is_empty());
+                assert_eq!(merged.states().len(), automaton.states().len());
+            },
+        );
+
+        // both of them: states of `C` are merged, those reducing `A` and `B` are not
+        check(
+            "a : 'a' ; b : 'b' ; c : 'c' ; d : 'd' ; e : 'e' ; x : 'x' ; y : 'y' ;",
+            "S : a A d | b B d | a B e | b A e | C C ; A : c ; B : c ; C : x C | y ;",
+            |automaton| {
+                let states = automaton.states().len();
+                assert_eq!(lalr(automaton).iter().max(), Some(&(states - 5)));
+
+                let blocks = minimal(automaton);
+                assert_eq!(blocks.iter().max(), Some(&(states - 4)));
+                assert_eq!(added_conflicts(automaton, &blocks), 0);
+                assert!(Parser::new(&automaton.merge(&blocks)).1.is_empty());
+            },
+        );
+    }
+}
@@ -0,0 +1,19 @@
+mod analysis;
+mod ast;
+mod automaton;
+mod compress;
+mod conflict;
+mod counterexample;
+mod grammar;
+mod inline;
+mod lower;
+mod merge;
+mod table;
+
+pub use analysis::*;
+pub use ast::*;
+pub use automaton::*;
+pub use grammar::*;
+pub use inline::*;
+pub use merge::*;
+pub use table::*;
@@ -0,0 +1,280 @@
+use crate::parse::automaton::Automaton;
+use crate::parse::compress::{most, Table};
+use crate::parse::conflict::{Conflict, Resolution};
+use crate::parse::grammar::{Grammar, Symbol};
+use crate::pattern::Assoc;
+use crate::util::TokenKind;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+#[derive(Serialize, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Action {
+    Error,
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+#[derive(Serialize)]
+pub struct Production {
+    head: usize,
+    len: usize,
+    captures: Vec<Option<usize>>,
+}
+
+#[derive(Serialize)]
+pub struct Nonterminal {
+    name: String,
+    helper: bool,
+}
+
+// ACTION and GOTO tables; mirrors `clr1::parse::Parser`
+#[derive(Serialize)]
+pub struct Parser {
+    // token kind of each terminal, `None` for end of input
+    terminals: Vec<Option<TokenKind>>,
+    // terminal by index of token kind, so that parser finds it without search; see `index_kinds`
+    columns: Vec<Option<usize>>,
+    nonterminals: Vec<Nonterminal>,
+    productions: Vec<Production>,
+    // names in `@name`, which productions refer to
+    captures: Vec<String>,
+    // terminal of `error`, if grammar has one
+    error: Option<usize>,
+    // [state][terminal], most frequent reduction of state is default
+    action: Table<Action>,
+    // [state][nonterminal]
+    goto: Table<Option<usize>>,
+}
+
+impl Parser {
+    // conflicts are resolved as yacc does: by precedence if both production and lookahead have one,
+    // otherwise shift wins over reduce, and earlier production wins
+    pub fn new(automaton: &Automaton) -> (Self, Vec<Conflict>) {
+        let grammar = automaton.grammar();
+
+        let terminals = grammar.terminals().iter().map(|t| t.kind().map(TokenKind)).collect::<Vec<_>>();
+        let nonterminals = grammar
+            .nonterminals()
+            .iter()
+            .enumerate()
+            .map(|(i, n)| Nonterminal {
+                name: grammar.name(Symbol::Nonterminal(i)).to_string(),
+                helper: n.helper(),
+            })
+            .collect();
+        let productions = grammar
+            .productions()
+            .iter()
+            .map(|p| Production {
+                head: p.head(),
+                len: p.body().len(),
+                captures: p.captures().to_vec(),
+            })
+            .collect();
+
+        let mut action = Vec::new();
+        let mut goto = Vec::new();
+        let mut conflicts = Vec::new();
+        // errors by `%nonassoc`, which default reduction must not take over
+        let mut errors = HashSet::new();
+        for (i, state) in automaton.states().iter().enumerate() {
+            let mut row = vec![Action::Error; terminals.len()];
+            let mut gotos = vec![None; grammar.nonterminals().len()];
+
+            for (&symbol, &target) in state.transitions() {
+                match symbol {
+                    Symbol::Terminal(terminal) => row[terminal] = Action::Shift(target),
+                    Symbol::Nonterminal(nonterminal) => gotos[nonterminal] = Some(target),
+                }
+            }
+
+            let mut reduces = vec![Vec::new(); terminals.len()];
+            for item in state.items() {
+                if item.next(grammar).is_none() {
+                    reduces[item.lookahead()].push(*item);
+                }
+            }
+
+            for (terminal, reduces) in reduces.into_iter().enumerate() {
+                let Some(item) = reduces.iter().min_by_key(|item| item.production()) else {
+                    continue;
+                };
+
+                let shifting = matches!(row[terminal], Action::Shift(_));
+                let resolution = match reduces.as_slice() {
+                    [item] if shifting => resolve(grammar, item.production(), terminal),
+                    _ => None,
+                };
+
+                if shifting || reduces.len() > 1 {
+                    // one item per core; lookaheads of shifting items are irrelevant
+                    let mut shifts = state
+                        .items()
+                        .iter()
+                        .filter(|item| item.next(grammar) == Some(Symbol::Terminal(terminal)))
+                        .copied()
+                        .collect::<Vec<_>>();
+                    shifts.sort_by_key(|item| (item.production(), item.dot()));
+                    shifts.dedup_by_key(|item| (item.production(), item.dot()));
+
+                    conflicts.push(Conflict::new(i, terminal, shifts, reduces.clone(), resolution));
+                }
+
+                let reduce = if grammar.productions()[item.production()].head() == Grammar::ACCEPT {
+                    Action::Accept
+                } else {
+                    Action::Reduce(item.production())
+                };
+
+                match resolution {
+                    Some(Resolution::Shift) => {}
+                    Some(Resolution::Reduce) => row[terminal] = reduce,
+                    Some(Resolution::Error) => {
+                        row[terminal] = Action::Error;
+                        errors.insert((i, terminal));
+                    }
+                    None if shifting => {}
+                    None => row[terminal] = reduce,
+                }
+            }
+
+            action.push(row);
+            goto.push(gotos);
+        }
+
+        // reducing on unexpected token instead of failing is harmless, as error is still detected before
+        // next shift. same for GOTO, whose missing entries are never looked up.
+        // but state shifting `error` must detect it before reducing, to be there to recover in, as bison does
+        let error = grammar.error_terminal();
+        let action = Table::new(
+            &action,
+            |row| match error.map(|error| row[error]) {
+                Some(Action::Shift(_)) => Action::Error,
+                _ => most(row, |action| matches!(action, Action::Reduce(_))).unwrap_or(Action::Error),
+            },
+            |state, terminal, action| action != Action::Error || errors.contains(&(state, terminal)),
+        );
+        let goto = Table::new(
+            &goto,
+            |row| most(row, |goto| goto.is_some()).unwrap_or(None),
+            |_, _, goto| goto.is_some(),
+        );
+
+        let parser = Self {
+            columns: columns(&terminals, Some),
+            terminals,
+            nonterminals,
+            productions,
+            captures: grammar.captures().to_vec(),
+            error: grammar.error_terminal(),
+            action,
+            goto,
+        };
+
+        (parser, conflicts)
+    }
+
+    // by default, index of token kind is kind itself. generated `TokenKind` is indexed by its discriminant instead,
+    // as given by `index`; `None` if kind has no variant
+    pub fn index_kinds(&mut self, index: impl Fn(usize) -> Option<usize>) {
+        self.columns = columns(&self.terminals, index);
+    }
+
+    // bytes of ACTION and GOTO, as uncompressed and as compressed
+    pub fn size(&self) -> (usize, usize) {
+        let (action, packed_action) = self.action.size();
+        let (goto, packed_goto) = self.goto.size();
+        (action + goto, packed_action + packed_goto)
+    }
+}
+
+// terminal of each token kind by its index; end of input has none, as it is always terminal 0
+fn columns(terminals: &[Option<TokenKind>], index: impl Fn(usize) -> Option<usize>) -> Vec<Option<usize>> {
+    let mut columns = Vec::new();
+    for (terminal, kind) in terminals.iter().enumerate() {
+        if let Some(i) = kind.and_then(|kind| index(kind.0)) {
+            if columns.len() <= i {
+                columns.resize(i + 1, None);
+            }
+            columns[i] = Some(terminal);
+        }
+    }
+    columns
+}
+
+fn resolve(grammar: &Grammar, production: usize, terminal: usize) -> Option<Resolution> {
+    let (rule, _) = grammar.productions()[production].precedence()?;
+    let (token, assoc) = grammar.precedence(terminal)?;
+
+    let resolution = match rule.cmp(&token) {
+        Ordering::Greater => Resolution::Reduce,
+        Ordering::Less => Resolution::Shift,
+        Ordering::Equal => match assoc {
+            Assoc::Left => Resolution::Reduce,
+            Assoc::Right => Resolution::Shift,
+            Assoc::Nonassoc => Resolution::Error,
+        },
+    };
+
+    Some(resolution)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Lexer;
+    use crate::parse::Analysis;
+    use crate::pattern::rules;
+    use crate::ser;
+    use crate::store::Store;
+
+    #[test]
+    fn test() {
+        let mut store = Store::new();
+        let mut lexer = Lexer::parse("d : 'd' ;", &mut store).unwrap();
+
+        let rules = rules(
+            r#"
+S : C C ;
+C : 'c' C | d ;
+            "#,
+            &mut store,
+        )
+        .unwrap();
+
+        let grammar = Grammar::new(&rules, &mut lexer, &mut store).unwrap();
+        let automaton = Automaton::new(&Analysis::new(&grammar));
+        let (mut parser, conflicts) = Parser::new(&automaton);
+        assert!(conflicts.is_empty());
+
+        // as it is generated
+        let generated = ser::to_string(&parser, Default::default()).unwrap();
+        let terminals = "Parser {\n    terminals: &[\n        None,\n        Some(0),\n        Some(5)\n    ],";
+        assert!(generated.starts_with(terminals));
+        assert!(generated.contains("Nonterminal {\n            name: \"C\",\n            helper: false\n        }"));
+        assert!(generated.contains("    captures: &[],\n    error: None,\n    action: Table {\n        rows: &["));
+
+        // $, d, 'c'
+        assert_eq!(parser.terminals.len(), 3);
+        assert_eq!(parser.columns, [Some(1), None, None, None, None, Some(2)]);
+        parser.index_kinds(|kind| [1, 0].get(kind / 5).copied());
+        assert_eq!(parser.columns, [Some(2), Some(1)]);
+
+        let states = automaton.states().len();
+        assert_eq!(states, 10);
+
+        let actions = (0..states).flat_map(|state| (0..3).map(move |terminal| (state, terminal)));
+        let accepts = actions.filter(|&(state, terminal)| parser.action.get(state, terminal) == Action::Accept);
+        assert_eq!(accepts.count(), 1);
+
+        assert!(matches!(parser.action.get(0, 0), Action::Error));
+        assert!(matches!(parser.action.get(0, 1), Action::Shift(_)));
+        assert!(matches!(parser.action.get(0, 2), Action::Shift(_)));
+        assert!(parser.goto.get(0, 1).is_some());
+
+        let (dense, packed) = parser.size();
+        assert!(packed < dense);
+    }
+}
@@ -1,5 +1,8 @@
+#[allow(clippy::module_inception)]
 mod pattern;
 mod parse;
+mod rule;
 
 pub use parse::*;
 pub use pattern::*;
+pub use rule::*;
@@ -1,9 +1,11 @@
+use std::borrow::Cow;
 use std::ops::RangeInclusive;
 use std::str::FromStr;
 use crate::error::{Error, ErrorKind};
 use crate::iter::{read_while, skip_while, Chars, Offset};
-use crate::pattern::pattern::{Class, ClassItem, Pattern, Quantifier};
+use crate::pattern::pattern::{Class, ClassItem, Mode, Pattern, Quantifier};
 use crate::store::Store;
+use crate::util::Boxed;
 
 #[inline]
 fn unreachable() -> ! {
@@ -15,11 +17,11 @@ fn unreachable() -> ! {
 
 fn escape<'a>(from: &mut dyn Chars<'a>, begin: Offset) -> Result<char, Error> {
     fn nibble_to_int(b: char) -> Option<u8> {
-        let c = if '0' <= b && b <= '9' {
+        let c = if b.is_ascii_digit() {
             b as u8 - b'0'
-        } else if 'a' <= b && b <= 'f' {
+        } else if ('a'..='f').contains(&b) {
             b as u8 - b'a' + 10
-        } else if 'A' <= b && b <= 'F' {
+        } else if ('A'..='F').contains(&b) {
             b as u8 - b'A' + 10
         } else {
             return None;
@@ -73,6 +75,7 @@ fn escape<'a>(from: &mut dyn Chars<'a>, begin: Offset) -> Result<char, Error> {
         Some((_, 'r')) => '\x0D',
         Some((_, 't')) => '\x09',
         Some((_, 'v')) => '\x0B',
+        Some((_, 'f')) => '\x0C',
         Some((_, '\\')) => '\\',
         Some((_, '\'')) => '\'',
         Some((_, '{')) => '{',
@@ -95,11 +98,11 @@ fn escape<'a>(from: &mut dyn Chars<'a>, begin: Offset) -> Result<char, Error> {
 }
 
 pub trait Parse<'a, T> {
-    fn parse(from: &mut dyn Chars<'a>, begin: Offset, store: &mut Store<&'a str>) -> Result<T, Error>;
+    fn parse(from: &mut dyn Chars<'a>, begin: Offset, store: &mut Store<Cow<'a, str>>) -> Result<T, Error>;
 }
 
 impl<'a> Parse<'a, Class> for Class {
-    fn parse(from: &mut dyn Chars<'a>, begin: Offset, _: &mut Store<&'a str>) -> Result<Self, Error> {
+    fn parse(from: &mut dyn Chars<'a>, begin: Offset, _: &mut Store<Cow<'a, str>>) -> Result<Self, Error> {
         fn parse_item(from: &mut dyn Chars) -> Result<ClassItem, Error> {
             let (begin, ch) = match from.next() {
                 // should be checked in Class::parse
@@ -150,7 +153,7 @@ impl<'a> Parse<'a, Class> for Class {
 }
 
 impl<'a> Parse<'a, RangeInclusive<usize>> for Quantifier {
-    fn parse(from: &mut dyn Chars<'a>, begin: Offset, _: &mut Store<&'a str>) -> Result<RangeInclusive<usize>, Error> {
+    fn parse(from: &mut dyn Chars<'a>, begin: Offset, _: &mut Store<Cow<'a, str>>) -> Result<RangeInclusive<usize>, Error> {
         let (min, max_required) = match from.peek() {
             None => return Err(ErrorKind::UnclosedQuantifier.at(begin)),
             Some((_, ',')) => {
@@ -205,7 +208,7 @@ impl<'a> Parse<'a, RangeInclusive<usize>> for Quantifier {
 }
 
 impl<'a> Parse<'a, Vec<Pattern>> for Vec<Pattern> {
-    fn parse(from: &mut dyn Chars<'a>, begin: Offset, store: &mut Store<&'a str>) -> Result<Vec<Pattern>, Error> {
+    fn parse(from: &mut dyn Chars<'a>, begin: Offset, store: &mut Store<Cow<'a, str>>) -> Result<Vec<Pattern>, Error> {
         let mut items = Vec::new();
         loop {
             skip_while(from, is_whitespace);
@@ -214,7 +217,7 @@ impl<'a> Parse<'a, Vec<Pattern>> for Vec<Pattern> {
                 None => break Err(ErrorKind::UnclosedGroup.at(begin)),
                 Some((_, ')')) => {
                     from.next().unwrap();
-                    break Ok(items);
+                    break Ok(finish(items, begin));
                 },
                 Some(_) => {}
             };
@@ -227,7 +230,7 @@ impl<'a> Parse<'a, Vec<Pattern>> for Vec<Pattern> {
 }
 
 impl<'a> Parse<'a, String> for String {
-    fn parse(from: &mut dyn Chars<'a>, begin: Offset, _: &mut Store<&'a str>) -> Result<String, Error> {
+    fn parse(from: &mut dyn Chars<'a>, begin: Offset, _: &mut Store<Cow<'a, str>>) -> Result<String, Error> {
         let mut buffer = String::new();
         loop {
             let ch = match from.next() {
@@ -251,15 +254,54 @@ pub fn is_whitespace(ch: char) -> bool {
     ch == ' ' || ('\t'..='\r').contains(&ch)
 }
 
+#[inline]
+pub fn is_ident(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+// pending `Or` is not a pattern by itself; `a | *` has nothing to quantify
 macro_rules! pop {
     ($from:expr, $offset:expr) => {
-        $from
-            .pop()
-            .ok_or_else(|| ErrorKind::MissingPrefix.at($offset))
+        match $from.pop() {
+            None | Some(Pattern::Or(..)) => Err(ErrorKind::MissingPrefix.at($offset)),
+            Some(pat) => Ok(pat),
+        }
     };
 }
 
-fn parse_once<'a>(from: &mut dyn Chars<'a>, stack: &mut Vec<Pattern>, store: &mut Store<&'a str>) -> Result<bool, Error> {
+// `|` binds looser than sequence: `a b | c` is `(a b) | c`.
+// While parsing, the alternatives seen so far are kept as `Or` at the bottom of the stack,
+// followed by items of the alternative being parsed. Alternative can be empty.
+fn alternate(stack: &mut Vec<Pattern>) -> Vec<Pattern> {
+    let mut alternatives = match stack.first() {
+        Some(Pattern::Or(..)) => match stack.remove(0) {
+            Pattern::Or(alternatives, _) => alternatives,
+            _ => unreachable(),
+        },
+        _ => Vec::new(),
+    };
+
+    let mut sequence = std::mem::take(stack);
+    alternatives.push(if sequence.len() == 1 {
+        sequence.pop().unwrap()
+    } else {
+        Pattern::Group(sequence)
+    });
+
+    alternatives
+}
+
+// `begin` is where group (or whole pattern) begins
+fn finish(mut stack: Vec<Pattern>, begin: Offset) -> Vec<Pattern> {
+    if let Some(Pattern::Or(..)) = stack.first() {
+        let alternatives = alternate(&mut stack);
+        stack.push(Pattern::Or(alternatives, begin));
+    }
+
+    stack
+}
+
+fn parse_once<'a>(from: &mut dyn Chars<'a>, stack: &mut Vec<Pattern>, store: &mut Store<Cow<'a, str>>) -> Result<bool, Error> {
     skip_while(from, is_whitespace);
 
     let str = from.as_str();
@@ -272,60 +314,61 @@ fn parse_once<'a>(from: &mut dyn Chars<'a>, stack: &mut Vec<Pattern>, store: &mu
         Some((_, '.')) => Class::new(true, vec![]).into(),
         Some((i, '[')) => Class::parse(from, i, store)?.into(),
         Some((i, '(')) => Vec::<Pattern>::parse(from, i, store).map(Pattern::Group)?,
-        Some((i, '|')) => {
-            let mut rhs = match stack.pop() {
-                None => return Err(ErrorKind::MissingPrefix.at(i)),
-                Some(Pattern::Or(rhs)) => rhs,
-                Some(rhs) => vec![rhs],
-            };
-
-            let mut local = Vec::new();
-            if !parse_once(from, &mut local, store)? {
-                return Err(ErrorKind::MissingSuffix.at(i));
-            };
-
-            let lhs = local.pop().unwrap();
+        // offset is set once group is finished
+        Some((i, '|')) => Pattern::Or(alternate(stack), i),
+
+        Some((i, '?')) => Quantifier::new(pop!(stack, i)?, 0..=1, i).into(),
+        Some((i, '*')) => Quantifier::new(pop!(stack, i)?, 0..=usize::MAX, i).into(),
+        Some((i, '+')) => Quantifier::new(pop!(stack, i)?, 1..=usize::MAX, i).into(),
+        Some((i, '{')) if is_quantifier(from.as_str()) => {
+            Quantifier::new(pop!(stack, i)?, Quantifier::parse(from, i, store)?, i).into()
+        }
+        Some((i, '{')) => Pattern::Code(code(from, i)?),
 
-            rhs.push(lhs);
+        Some((i, '%')) => match read_while(from, is_ident) {
+            Some((_, _, "prec")) => {
+                skip_while(from, is_whitespace);
 
-            Pattern::Or(rhs)
-        }
+                // literal or name of terminal whose precedence applies
+                let symbol = match from.peek() {
+                    Some((i, '\'')) => {
+                        from.next().unwrap();
+                        String::parse(from, i, store)?.into()
+                    }
+                    _ => match read_while(from, is_ident) {
+                        Some((_, _, name)) => Pattern::Reference(store.add(name), None),
+                        None => return Err(ErrorKind::MissingSuffix.at(i)),
+                    },
+                };
 
-        Some((i, '?')) => Quantifier::new(pop!(stack, i)?, 0..=1).into(),
-        Some((i, '*')) => Quantifier::new(pop!(stack, i)?, 0..=usize::MAX).into(),
-        Some((i, '+')) => Quantifier::new(pop!(stack, i)?, 1..=usize::MAX).into(),
-        Some((i, '{')) => Quantifier::new(pop!(stack, i)?, Quantifier::parse(from, i, store)?).into(),
+                Pattern::Prec(Boxed::new(symbol))
+            }
+            Some((_, _, "push")) => Mode::Push(mode(from, i, store)?).into(),
+            Some((_, _, "pop")) => Mode::Pop.into(),
+            Some((_, _, "switch")) => Mode::Switch(mode(from, i, store)?).into(),
+            Some((_, _, name)) => return Err(ErrorKind::UnknownDirective(name.to_string()).at(i)),
+            None => return Err(ErrorKind::EmptyName.at(i)),
+        },
 
         Some((i, ')' | ']' | '}')) => {
             return Err(ErrorKind::CloserMismatched.at(i));
         }
 
-        Some((i, ch)) => {
-            let mut size = ch.len_utf8();
-            let (named, offset) = loop {
-                match from.next() {
-                    None => break (false, i),
-                    Some((offset, '@')) => break (true, offset),
-                    Some((offset, ch)) => {
-                        if ch.is_alphanumeric() || ch == '_' {
-                            size += ch.len_utf8()
-                        } else {
-                            break (false, offset)
-                        }
-                    }
-                }
-            };
-
+        Some((_, ch)) => {
+            let size = ch.len_utf8() + read_while(from, is_ident).map_or(0, |(_, _, s)| s.len());
             let reference = store.add(&str[..size]);
 
-            let name = if named {
-                let Some((_, _, name)) = read_while(from, |ch: char| !is_whitespace(ch)) else {
-                    return Err(ErrorKind::EmptyName.at(offset))
-                };
+            let name = match from.peek() {
+                Some((offset, '@')) => {
+                    from.next().unwrap();
 
-                Some(store.add(name))
-            } else {
-                None
+                    let Some((_, _, name)) = read_while(from, is_ident) else {
+                        return Err(ErrorKind::EmptyName.at(offset))
+                    };
+
+                    Some(store.add(name))
+                }
+                _ => None,
             };
 
             Pattern::Reference(reference, name)
@@ -337,8 +380,100 @@ fn parse_once<'a>(from: &mut dyn Chars<'a>, stack: &mut Vec<Pattern>, store: &mu
     Ok(true)
 }
 
-pub fn parse<'a>(from: &mut dyn Chars<'a>, store: &mut Store<&'a str>) -> Result<Pattern, Error> {
+// `{2,3}` is quantifier, but `{ 2 }` is code
+fn is_quantifier(str: &str) -> bool {
+    str.find('}').is_some_and(|end| end > 0 && str[..end].chars().all(|ch| ch.is_ascii_digit() || ch == ','))
+}
+
+// length of string or char literal, or comment, which `str` of rust code starts with;
+// its braces do not count, nor does `$name` in it bind
+pub fn rust_literal(str: &str) -> Option<usize> {
+    // of byte, C and raw strings, and of byte chars
+    let prefix = ["br", "cr", "b", "c", "r"].into_iter().find(|prefix| str.starts_with(prefix)).unwrap_or("");
+    let rest = &str[prefix.len()..];
+
+    // `r#"..."#` ends at quote with as many hashes, and has no escapes; `r#type` is raw identifier
+    if prefix.ends_with('r') {
+        let hashes = rest.len() - rest.trim_start_matches('#').len();
+        let body = rest[hashes..].strip_prefix('"')?;
+        let close = format!("\"{}", "#".repeat(hashes));
+        let len = body.find(&close).map_or(body.len(), |end| end + close.len());
+        return Some(prefix.len() + hashes + 1 + len);
+    }
+
+    let mut chars = rest.char_indices();
+    let end = match chars.next()? {
+        (_, '"') => {
+            let mut escaped = false;
+            chars.find(|&(_, ch)| {
+                let end = ch == '"' && !escaped;
+                escaped = ch == '\\' && !escaped;
+                end
+            })
+        }
+        // `'a` may be lifetime
+        (_, '\'') if matches!(prefix, "" | "b") => match (chars.next()?, chars.next()?) {
+            ((_, '\\'), _) => chars.find(|&(_, ch)| ch == '\''),
+            (_, (i, '\'')) => Some((i, '\'')),
+            _ => return None,
+        },
+        (_, '/') if prefix.is_empty() => match chars.next()? {
+            (_, '/') => chars.find(|&(_, ch)| ch == '\n'),
+            (_, '*') => return Some(str.find("*/").map_or(str.len(), |end| end + 2)),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(end.map_or(str.len(), |(i, ch)| prefix.len() + i + ch.len_utf8()))
+}
+
+// rust code up to matching '}', without braces
+fn code<'a>(from: &mut dyn Chars<'a>, begin: Offset) -> Result<String, Error> {
+    let str = from.as_str();
+    let mut depth = 0;
+    let mut i = 0;
+    let end = loop {
+        let Some(ch) = str[i..].chars().next() else {
+            return Err(ErrorKind::UnclosedCode.at(begin));
+        };
+
+        if let Some(len) = rust_literal(&str[i..]) {
+            i += len;
+            continue;
+        }
+
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => break i,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        i += ch.len_utf8();
+    };
+
+    // `Chars` keeps track of offset
+    while from.as_str().len() > str.len() - end - 1 {
+        from.next().unwrap();
+    }
+
+    Ok(str[..end].trim().to_string())
+}
+
+// name of mode following `%push` or `%switch`
+fn mode<'a>(from: &mut dyn Chars<'a>, begin: Offset, store: &mut Store<Cow<'a, str>>) -> Result<usize, Error> {
+    skip_while(from, is_whitespace);
+    match read_while(from, is_ident) {
+        Some((_, _, name)) => Ok(store.add(name)),
+        None => Err(ErrorKind::MissingSuffix.at(begin)),
+    }
+}
+
+pub fn parse<'a>(from: &mut dyn Chars<'a>, store: &mut Store<Cow<'a, str>>) -> Result<Pattern, Error> {
+    skip_while(from, is_whitespace);
+
+    let begin = from.peek().map_or(Offset::new(0, 0), |(offset, _)| offset);
     let mut list: Vec<Pattern> = Vec::new();
     while parse_once(from, &mut list, store)? {}
-    Ok(Pattern::Group(list))
+    Ok(Pattern::Group(finish(list, begin)))
 }
@@ -1,3 +1,4 @@
+use crate::iter::Offset;
 use crate::util::Boxed;
 use derive_more::with_trait::From;
 use serde::Serialize;
@@ -5,7 +6,7 @@ use std::ops::RangeInclusive;
 
 // [LIST]
 // [^LIST]
-#[derive(Serialize)]
+#[derive(Clone)]
 pub struct Class {
     deny: bool,
     list: Vec<ClassItem>,
@@ -15,9 +16,17 @@ impl Class {
     pub fn new(deny: bool, list: Vec<ClassItem>) -> Self {
         Self { deny, list }
     }
+
+    pub fn deny(&self) -> bool {
+        self.deny
+    }
+
+    pub fn list(&self) -> &[ClassItem] {
+        &self.list
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Clone)]
 pub enum ClassItem {
     Char(char),
     Range(RangeInclusive<char>),
@@ -28,22 +37,37 @@ pub enum ClassItem {
 // PAT+
 // PAT{n,m?}
 // PAT{,m}
-#[derive(Serialize)]
+#[derive(Clone)]
 pub struct Quantifier {
     range: RangeInclusive<usize>,
     what: Boxed<Pattern>,
+    // of quantifier, where helper it is lowered into is reported
+    at: Offset,
 }
 
 impl Quantifier {
-    pub fn new(pat: Pattern, range: RangeInclusive<usize>) -> Self {
+    pub fn new(pat: Pattern, range: RangeInclusive<usize>, at: Offset) -> Self {
         Self {
             range,
             what: Boxed::new(pat),
+            at,
         }
     }
+
+    pub fn at(&self) -> Offset {
+        self.at
+    }
+
+    pub fn range(&self) -> &RangeInclusive<usize> {
+        &self.range
+    }
+
+    pub fn what(&self) -> &Pattern {
+        &self.what
+    }
 }
 
-#[derive(Serialize, From)]
+#[derive(Clone, From)]
 pub enum Pattern {
     Reference(usize, Option<usize>),
     Literal(String),
@@ -51,6 +75,24 @@ pub enum Pattern {
     Quantifier(Quantifier),
     #[from(skip)]
     Group(Vec<Pattern>),
+    // alternatives, and offset of group they are in
+    #[from(skip)]
+    Or(Vec<Pattern>, Offset),
+    // %prec <symbol>, only at end of alternative of parser rule
     #[from(skip)]
-    Or(Vec<Pattern>),
+    Prec(Boxed<Pattern>),
+    // %push <mode>, %pop, %switch <mode>, only at end of lexer rule
+    Mode(Mode),
+    // { <rust code> }, only at end of alternative of parser rule
+    #[from(skip)]
+    Code(String),
+}
+
+// change of lexer mode after token of rule; mirrors `clr1::lex::Mode`.
+// mode is name of mode in pattern, and index of mode in lexer rule.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum Mode {
+    Push(usize),
+    Pop,
+    Switch(usize),
 }
@@ -0,0 +1,261 @@
+use crate::error::{Error, ErrorKind};
+use crate::iter::{read_while, skip_while, Chars, Iter, Offset};
+use crate::pattern::{is_ident, is_whitespace, parse, Pattern};
+use crate::store::Store;
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+// <kind>? <name> : <pattern> ;
+pub struct Definition {
+    pub at: Offset,
+    pub kind: Kind,
+    // name of mode of latest `%mode`, if any
+    pub mode: Option<usize>,
+    pub name: usize,
+    pub pat: Pattern,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Kind {
+    #[default]
+    Token,
+    // lexer rule which is only referred to by other lexer rules, never a token by itself
+    Fragment,
+    // lexer rule whose tokens are discarded
+    Skip,
+    // lexer rule whose tokens are kept as trivia of neighbouring tokens, but not given to parser
+    Hidden,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Token => write!(f, "token"),
+            Kind::Fragment => write!(f, "fragment"),
+            Kind::Skip => write!(f, "skip"),
+            Kind::Hidden => write!(f, "hidden"),
+        }
+    }
+}
+
+impl Kind {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "fragment" => Some(Self::Fragment),
+            "skip" => Some(Self::Skip),
+            "hidden" => Some(Self::Hidden),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+    Nonassoc,
+}
+
+// %left <symbol>... ;
+// later declaration binds tighter
+pub struct Precedence {
+    pub at: Offset,
+    pub assoc: Assoc,
+    // literal or reference
+    pub symbols: Vec<Pattern>,
+}
+
+// %mode <name> ;
+// rules after it belong to mode, until next one
+pub struct Section {
+    pub at: Offset,
+    pub mode: usize,
+}
+
+// %type <type> <name>... ;
+// what code blocks of rules evaluate to
+pub struct Type {
+    pub at: Offset,
+    // rust type, as is
+    pub ty: String,
+    pub names: Vec<usize>,
+}
+
+enum Directive {
+    Precedence(Precedence),
+    Section(Section),
+    Type(Type),
+}
+
+pub struct Rules {
+    pub definitions: Vec<Definition>,
+    pub precedences: Vec<Precedence>,
+    pub sections: Vec<Section>,
+    pub types: Vec<Type>,
+}
+
+pub fn rules<'a>(from: &'a str, store: &mut Store<Cow<'a, str>>) -> Result<Rules, Error> {
+    let mut from: Iter<'a> = from.into();
+
+    let mut definitions = Vec::new();
+    let mut precedences = Vec::new();
+    let mut sections: Vec<Section> = Vec::new();
+    let mut types = Vec::new();
+    loop {
+        skip_while(&mut from, is_whitespace);
+
+        match from.peek() {
+            Some((_, '#')) => {
+                from.next().unwrap();
+                skip_while(&mut from, |ch: char| ch != '\n');
+                continue;
+            }
+            Some((begin, '%')) => {
+                from.next().unwrap();
+                match directive(&mut from, begin, store)? {
+                    Directive::Precedence(precedence) => precedences.push(precedence),
+                    Directive::Section(section) => sections.push(section),
+                    Directive::Type(ty) => types.push(ty),
+                }
+                continue;
+            }
+            None => {
+                break Ok(Rules {
+                    definitions,
+                    precedences,
+                    sections,
+                    types,
+                });
+            }
+            _ => {}
+        }
+
+        // kind is told from name by ':' following name only
+        let mut kind = Kind::Token;
+        let mut after = None;
+        let (begin, end, name) = loop {
+            let Some((begin, end, name)) = read_while(&mut from, |ch: char| !is_whitespace(ch) && ch != ':') else {
+                return Err(match (from.peek(), after) {
+                    // starts with ':'
+                    (Some((offset, _)), _) => ErrorKind::EmptyName.at(offset),
+                    // kind only
+                    (None, Some(end)) => ErrorKind::UnexpectedEOF.at(end),
+                    (None, None) => unreachable!(),
+                });
+            };
+            skip_while(&mut from, is_whitespace);
+
+            match (kind, Kind::parse(name)) {
+                (Kind::Token, Some(modifier)) if !matches!(from.peek(), Some((_, ':'))) => {
+                    kind = modifier;
+                    after = Some(end);
+                }
+                _ => break (begin, end, name),
+            }
+        };
+        if name.contains([':', '@']) {
+            break Err(ErrorKind::InvalidRuleHead.at(begin));
+        }
+        let name = store.add(name);
+
+        // consume ':'
+        match from.next() {
+            Some((_, ':')) => {}
+            Some((offset, _)) => break Err(ErrorKind::Expected(':').at(offset)),
+            None => {
+                break Err(
+                    ErrorKind::Expected(':').at(Offset::new(end.line(), end.column() + 1))
+                );
+            }
+        }
+
+        let pat = parse(&mut from, store)?;
+
+        let mode = sections.last().map(|section| section.mode);
+        definitions.push(Definition { at: begin, kind, mode, name, pat });
+    }
+}
+
+fn directive<'a>(from: &mut Iter<'a>, begin: Offset, store: &mut Store<Cow<'a, str>>) -> Result<Directive, Error> {
+    let assoc = match read_while(from, is_ident) {
+        Some((_, _, "left")) => Assoc::Left,
+        Some((_, _, "right")) => Assoc::Right,
+        Some((_, _, "nonassoc")) => Assoc::Nonassoc,
+        Some((_, _, "mode")) => {
+            return match parse(from, store)? {
+                Pattern::Group(list) => match list.as_slice() {
+                    [Pattern::Reference(mode, None)] => Ok(Directive::Section(Section { at: begin, mode: *mode })),
+                    [] => Err(ErrorKind::MissingSuffix.at(begin)),
+                    _ => Err(ErrorKind::UnsupportedPattern.at(begin)),
+                },
+                _ => unreachable!(),
+            };
+        }
+        Some((_, _, "type")) => {
+            let ty = ty(from, begin)?;
+            let Pattern::Group(names) = parse(from, store)? else {
+                unreachable!()
+            };
+
+            let names = names
+                .iter()
+                .map(|pat| match pat {
+                    Pattern::Reference(name, None) => Ok(*name),
+                    _ => Err(ErrorKind::UnsupportedPattern.at(begin)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if names.is_empty() {
+                return Err(ErrorKind::MissingSuffix.at(begin));
+            }
+
+            return Ok(Directive::Type(Type { at: begin, ty, names }));
+        }
+        Some((_, _, name)) => return Err(ErrorKind::UnknownDirective(name.to_string()).at(begin)),
+        None => return Err(ErrorKind::EmptyName.at(begin)),
+    };
+
+    let Pattern::Group(symbols) = parse(from, store)? else {
+        unreachable!()
+    };
+
+    if symbols.is_empty() {
+        return Err(ErrorKind::MissingSuffix.at(begin));
+    }
+    if !symbols.iter().all(|pat| matches!(pat, Pattern::Literal(_) | Pattern::Reference(_, None))) {
+        return Err(ErrorKind::UnsupportedPattern.at(begin));
+    }
+
+    Ok(Directive::Precedence(Precedence { at: begin, assoc, symbols }))
+}
+
+// `<type>` of `%type`, which may have `<>` of its own, as in `<Vec<i64>>`
+fn ty(from: &mut Iter, begin: Offset) -> Result<String, Error> {
+    skip_while(from, is_whitespace);
+    match from.next() {
+        Some((_, '<')) => {}
+        Some((offset, _)) => return Err(ErrorKind::Expected('<').at(offset)),
+        None => return Err(ErrorKind::UnexpectedEOF.at(begin)),
+    }
+
+    let mut ty = String::new();
+    let mut depth = 0;
+    loop {
+        match from.next() {
+            None => return Err(ErrorKind::Expected('>').at(begin)),
+            Some((_, '>')) if depth == 0 && !ty.ends_with('-') => break,
+            Some((_, ch)) => {
+                match ch {
+                    '<' => depth += 1,
+                    '>' if !ty.ends_with('-') => depth -= 1,
+                    _ => {}
+                }
+                ty.push(ch);
+            }
+        }
+    }
+
+    match ty.trim() {
+        "" => Err(ErrorKind::MissingSuffix.at(begin)),
+        ty => Ok(ty.to_string()),
+    }
+}
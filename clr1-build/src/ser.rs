@@ -1,8 +1,8 @@
 use serde::{ser, Serialize};
+use std::collections::HashMap;
 use std::fmt::{Debug, Display};
 use std::io::Write;
 use std::string::FromUtf8Error;
-use serde::__private228::de::IdentifierDeserializer;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -24,15 +24,19 @@ impl ser::Error for Error {
 struct Ser<W: Write> {
     writer: W,
     in_range: bool,
+    in_kind: bool,
+    kinds: HashMap<usize, String>,
     indent: usize,
     stack: Vec<bool>,
 }
 
 impl<W: Write> Ser<W> {
-    fn new(writer: W, indent: usize) -> Ser<W> {
+    fn new(writer: W, indent: usize, kinds: HashMap<usize, String>) -> Ser<W> {
         Ser {
             writer,
             in_range: false,
+            in_kind: false,
+            kinds,
             indent,
             stack: vec![false],
         }
@@ -48,7 +52,7 @@ impl<W: Write> Ser<W> {
         }
 
         if indent {
-            write!(self.writer, "\n")?;
+            writeln!(self.writer)?;
             self.do_indent()?;
         } else {
             write!(self.writer, " ")?;
@@ -92,7 +96,7 @@ impl<W: Write> Ser<W> {
 
     fn outdent(&mut self) -> Result<(), Error> {
         if self.stack.pop() == Some(true) {
-            write!(self.writer, "\n")?;
+            writeln!(self.writer)?;
             self.do_indent()?;
         }
 
@@ -155,7 +159,10 @@ impl<W: Write> ser::Serializer for &mut Ser<W> {
         Ok(())
     }
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        write!(self.writer, "{}", v)?;
+        match self.kinds.get(&(v as usize)) {
+            Some(variant) if self.in_kind => write!(self.writer, "TokenKind::{}", variant)?,
+            _ => write!(self.writer, "{}", v)?,
+        }
         Ok(())
     }
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
@@ -175,19 +182,19 @@ impl<W: Write> ser::Serializer for &mut Ser<W> {
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
         match v {
             '\'' => {
-                self.writer.write(b"\\'")?;
+                self.writer.write_all(b"'\\''")?;
             }
             '\t' => {
-                self.writer.write(b"'\\t'")?;
+                self.writer.write_all(b"'\\t'")?;
             }
             '\n' => {
-                self.writer.write(b"'\\n'")?;
+                self.writer.write_all(b"'\\n'")?;
             }
             '\r' => {
-                self.writer.write(b"'\\r'")?;
+                self.writer.write_all(b"'\\r'")?;
             }
             '\\' => {
-                self.writer.write(b"'\\\\'")?;
+                self.writer.write_all(b"'\\\\'")?;
             }
             v => {
                 if v.is_control() {
@@ -205,19 +212,19 @@ impl<W: Write> ser::Serializer for &mut Ser<W> {
         for ch in v.chars() {
             match ch {
                 '"' => {
-                    self.writer.write(b"\\\"")?;
+                    self.writer.write_all(b"\\\"")?;
                 }
                 '\t' => {
-                    self.writer.write(b"\\t")?;
+                    self.writer.write_all(b"\\t")?;
                 }
                 '\n' => {
-                    self.writer.write(b"\\n")?;
+                    self.writer.write_all(b"\\n")?;
                 }
                 '\r' => {
-                    self.writer.write(b"\\r")?;
+                    self.writer.write_all(b"\\r")?;
                 }
                 '\\' => {
-                    self.writer.write(b"\\\\")?;
+                    self.writer.write_all(b"\\\\")?;
                 }
                 ch => {
                     if ch.is_control() {
@@ -235,7 +242,7 @@ impl<W: Write> ser::Serializer for &mut Ser<W> {
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
         write!(self.writer, "&[")?;
         for b in v {
-            write!(self.writer, "0x{:02x}u8", b)?;
+            write!(self.writer, "0x{:02x}u8, ", b)?;
         }
         write!(self.writer, "]")?;
         Ok(())
@@ -281,6 +288,10 @@ impl<W: Write> ser::Serializer for &mut Ser<W> {
         if name == "Boxed" {
             write!(self.writer, "&")?;
             self.serialize(value)?;
+        } else if name == "TokenKind" {
+            self.in_kind = true;
+            self.serialize(value)?;
+            self.in_kind = false;
         } else {
             write!(self.writer, "{}(", name)?;
             self.serialize(value)?;
@@ -497,11 +508,13 @@ impl<W: Write> ser::SerializeStructVariant for &mut Ser<W> {
 #[derive(Default)]
 pub struct Options {
     pub initial_indent: usize,
+    // variant of generated `TokenKind` by token kind, which `util::TokenKind` is written as
+    pub kinds: HashMap<usize, String>,
 }
 
 impl Options {
-    fn to_ser<W: Write>(&self, writer: W) -> Ser<W> {
-        Ser::new(writer, self.initial_indent)
+    fn into_ser<W: Write>(self, writer: W) -> Ser<W> {
+        Ser::new(writer, self.initial_indent, self.kinds)
     }
 }
 
@@ -510,7 +523,7 @@ pub fn serialize<T: ?Sized + Serialize, W: Write>(
     value: &T,
     options: Options,
 ) -> Result<(), Error> {
-    options.to_ser(writer).serialize(value)
+    options.into_ser(writer).serialize(value)
 }
 
 pub fn to_string<T: ?Sized + Serialize>(value: &T, options: Options) -> Result<String, Error> {
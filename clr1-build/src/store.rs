@@ -2,28 +2,30 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::ops::Index;
 
-pub struct Store<T: Eq + Hash> {
+pub struct Store<T: Eq + Hash + Clone> {
     map: HashMap<T, usize>,
     list: Vec<T>
 }
 
-impl<T: Eq + Hash> Store<T> {
+impl<T: Eq + Hash + Clone> Store<T> {
     pub fn new() -> Self {
         Self { map: HashMap::new(), list: Vec::new() }
     }
 
-    pub fn add(&mut self, v: T) -> usize {
+    pub fn add(&mut self, v: impl Into<T>) -> usize {
+        let v = v.into();
         if let Some(&i) = self.map.get(&v) {
             i
         } else {
             let i = self.list.len();
+            self.map.insert(v.clone(), i);
             self.list.push(v);
             i
         }
     }
 }
 
-impl<T: Eq + Hash> Index<usize> for Store<T> {
+impl<T: Eq + Hash + Clone> Index<usize> for Store<T> {
     type Output = T;
 
     fn index(&self, index: usize) -> &Self::Output {
@@ -1,6 +1,7 @@
 use serde::Serialize;
+use std::ops::Deref;
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct Boxed<T>(Box<T>);
 
 impl<T> Boxed<T> {
@@ -8,3 +9,15 @@ impl<T> Boxed<T> {
         Self(Box::new(value))
     }
 }
+
+impl<T> Deref for Boxed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// token kind, written as variant of generated `TokenKind` if serializer knows its name
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize)]
+pub struct TokenKind(pub usize);
@@ -5,8 +5,8 @@ use std::process::ExitCode;
 
 fn main() -> ExitCode {
     let base = Path::new(env!("CARGO_MANIFEST_DIR"));
-    let lexer = base.join("../test.lex").canonicalize().unwrap();
-    let parser = base.join("../test.parse").canonicalize().unwrap();
+    let lexer = base.join("test.lex").canonicalize().unwrap();
+    let parser = base.join("test.parse").canonicalize().unwrap();
 
     println!("cargo:rerun-if-changed={}", lexer.display());
     println!("cargo:rerun-if-changed={}", parser.display());
@@ -1,6 +1,71 @@
-
 include!(concat!(env!("OUT_DIR"), "/clr1.rs"));
 
+use clr1::lex::{Lines, Token};
+use clr1::parse::Node;
+use generated::{Actions, TokenKind};
+
 fn main() {
-    println!("Hello, world!");
+    // without arguments, tokens of standard input as they are read
+    if std::env::args().len() == 1 {
+        for item in generated::LEXER.stream(std::io::stdin().lock()) {
+            match item {
+                Ok(token) => println!("{:?} {} {:?}", token.span(), token.kind(), token.value()),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+
+    for arg in std::env::args().skip(1) {
+        let lines = Lines::new(&arg);
+        let (tokens, errors) = generated::LEXER.lex_recover(&arg);
+        for error in &errors {
+            eprintln!("{}: {}", lines.offset(error.at), error);
+        }
+
+        for token in &tokens {
+            println!("{} {} {:?}", lines.offset(token.span().start), token.kind(), token.value());
+        }
+
+        match generated::PARSER.parse_tree(tokens) {
+            Ok(tree) => {
+                print(tree.root(), 0);
+                println!("{:?}", generated::ast::Assign::from(tree.root()));
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+
+        if let Ok(value) = Describe.parse(generated::LEXER.lex_recover(&arg).0) {
+            println!("{}", value);
+        }
+    }
+}
+
+// input in words, in one pass
+struct Describe;
+
+impl<'a> Actions<'a> for Describe {
+    type Value = String;
+
+    fn reduce_Assign(&mut self, name: Token<'a, TokenKind>, value: String) -> String {
+        format!("{} is {}", name.value(), value)
+    }
+
+    fn reduce_Value_IPv4(&mut self, value: Token<'a, TokenKind>) -> String {
+        format!("address {}", value.value())
+    }
+
+    fn reduce_Value_Number(&mut self, value: Token<'a, TokenKind>) -> String {
+        format!("number {}", value.value())
+    }
+}
+
+fn print(node: Node<generated::TokenKind>, depth: usize) {
+    match node.token() {
+        Some(token) => println!("{:indent$}{} {:?}", "", token.kind(), token.value(), indent = depth * 2),
+        None => {
+            let nonterminal = &generated::PARSER.nonterminals[node.nonterminal().unwrap()];
+            println!("{:indent$}{}", "", nonterminal.name, indent = depth * 2);
+            node.children().for_each(|child| print(child, depth + 1));
+        }
+    }
 }
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+use std::ops::RangeInclusive;
+
+pub struct Transition {
+    pub range: RangeInclusive<char>,
+    pub target: usize,
+}
+
+// minimal DFA of all lexer rules; state 0 is start
+pub struct Dfa {
+    // transitions of state i are `transitions[offsets[i]..offsets[i + 1]]`, sorted by range
+    pub offsets: &'static [usize],
+    pub transitions: &'static [Transition],
+    // index of rule matched on reaching state
+    pub accept: &'static [Option<usize>],
+}
+
+impl Dfa {
+    fn next(&self, state: usize, ch: char) -> Option<usize> {
+        let transitions = &self.transitions[self.offsets[state]..self.offsets[state + 1]];
+        let i = transitions.partition_point(|transition| *transition.range.end() < ch);
+        transitions.get(i).filter(|transition| transition.range.contains(&ch)).map(|transition| transition.target)
+    }
+
+    // longest non-empty match as [rule index, length], in single pass
+    pub fn scan(&self, from: &str) -> Option<(usize, usize)> {
+        self.scan_more(from).0
+    }
+
+    // as `scan`, and whether DFA could go on at end of input, so more input might give longer match
+    pub fn scan_more(&self, from: &str) -> (Option<(usize, usize)>, bool) {
+        let mut state = 0;
+        let mut last = None;
+        for (i, ch) in from.char_indices() {
+            let Some(next) = self.next(state, ch) else {
+                return (last, false);
+            };
+
+            state = next;
+            if let Some(rule) = self.accept[state] {
+                last = Some((rule, i + ch.len_utf8()));
+            }
+        }
+
+        (last, self.offsets[state] < self.offsets[state + 1])
+    }
+
+    // offset of first char after first one where some rule matches, and whether DFA could go on at end of input
+    // from some offset before it, so more input might give earlier one.
+    // DFA is run from every offset at once, in single pass: runs in same state go on alike, so only earliest is kept
+    pub fn resync(&self, from: &str) -> (Option<usize>, bool) {
+        // state of each run, with offset it started at; earlier first, as runs are started in order
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        let mut next = Vec::new();
+        let mut found: Option<usize> = None;
+        for (i, ch) in from.char_indices() {
+            if i > 0 && found.is_none() && runs.iter().all(|&(state, _)| state != 0) {
+                runs.push((0, i));
+            }
+
+            next.clear();
+            for &(state, start) in &runs {
+                // runs from later offsets cannot give earlier one
+                if found.is_some_and(|found| found <= start) {
+                    break;
+                }
+
+                let Some(state) = self.next(state, ch) else {
+                    continue;
+                };
+
+                if self.accept[state].is_some() {
+                    found = Some(start);
+                } else if next.iter().all(|&(other, _)| other != state) {
+                    next.push((state, start));
+                }
+            }
+            std::mem::swap(&mut runs, &mut next);
+
+            if found.is_some() && runs.is_empty() {
+                return (found, false);
+            }
+        }
+
+        let more = runs.iter().any(|&(state, _)| self.offsets[state] < self.offsets[state + 1]);
+        (found, more)
+    }
+
+    // rules still possible after longest prefix DFA follows without accepting; none if first char leads nowhere
+    pub fn closest(&self, from: &str) -> Vec<usize> {
+        let mut state = 0;
+        let mut moved = false;
+        for ch in from.chars() {
+            let Some(next) = self.next(state, ch) else {
+                break;
+            };
+
+            state = next;
+            moved = true;
+        }
+
+        if !moved {
+            return Vec::new();
+        }
+
+        let mut seen = BTreeSet::from([state]);
+        let mut queue = vec![state];
+        let mut rules = BTreeSet::new();
+        while let Some(state) = queue.pop() {
+            rules.extend(self.accept[state]);
+            for transition in &self.transitions[self.offsets[state]..self.offsets[state + 1]] {
+                if seen.insert(transition.target) {
+                    queue.push(transition.target);
+                }
+            }
+        }
+
+        rules.into_iter().collect()
+    }
+}
@@ -0,0 +1,45 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Cause {
+    // no rule of current mode matches
+    Unmatched,
+    // token would pop first mode
+    Pop,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LexError<K = usize> {
+    pub cause: Cause,
+    // byte offset of `found`
+    pub at: usize,
+    // unmatched input, up to where some rule of that mode matches again; token itself for `Cause::Pop`
+    pub span: Range<usize>,
+    pub found: char,
+    // token kinds of rules which matched longest part of input at `at` without failing, if any;
+    // only `at` is tried, not offsets after it within `span`
+    pub closest: Vec<K>,
+}
+
+// without offset; see `Lines` for line and column
+impl<K: Display> Display for LexError<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.cause {
+            Cause::Unmatched => write!(f, "unexpected character {:?}", self.found)?,
+            Cause::Pop => write!(f, "unexpected character {:?}, as there is no mode to return to", self.found)?,
+        }
+
+        if let Some((first, rest)) = self.closest.split_first() {
+            write!(f, " (closest: {}", first)?;
+            for kind in rest {
+                write!(f, ", {}", kind)?;
+            }
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<K: Debug + Display> std::error::Error for LexError<K> {}
@@ -1,65 +1,412 @@
-use crate::lex::consume::Consume;
-use crate::lex::pattern::Pattern;
+use crate::lex::dfa::Dfa;
+use crate::lex::error::{Cause, LexError};
+use crate::lex::stream::{Stream, Tokens};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::ops::Range;
 
-pub struct Rule {
-    pub id: usize,
-    pub pat: Pattern,
+// where tokens of rule go
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Channel {
+    // given to parser
+    Token,
+    // discarded
+    Skip,
+    // kept as trivia of neighbouring token
+    Hidden,
 }
 
-pub struct Token<'a> {
-    kind: usize,
-    value: &'a str,
+// change of mode after token of rule
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Push(usize),
+    Pop,
+    Switch(usize),
 }
 
-impl<'a> Token<'a> {
-    fn new(kind: usize, value: &'a str) -> Self {
-        Self { kind, value }
+// `K` is token kind; generated `TokenKind` enum, or plain index
+pub struct Rule<K: 'static = usize> {
+    pub id: K,
+    pub channel: Channel,
+    pub mode: Option<Mode>,
+}
+
+pub struct Token<'a, K = usize> {
+    kind: K,
+    // borrowed from input, or owned when lexing from reader
+    value: Cow<'a, str>,
+    // byte range in input
+    span: Range<usize>,
+    // hidden tokens right before this one
+    leading: Vec<Token<'a, K>>,
+    // hidden tokens after this one, if it is last
+    trailing: Vec<Token<'a, K>>,
+}
+
+impl<'a, K: Copy> Token<'a, K> {
+    pub(crate) fn new(kind: K, value: &'a str, at: usize) -> Self {
+        Self {
+            kind,
+            value: Cow::Borrowed(value),
+            span: at..at + value.len(),
+            leading: Vec::new(),
+            trailing: Vec::new(),
+        }
     }
 
-    pub fn kind(&self) -> usize {
+    pub fn kind(&self) -> K {
         self.kind
     }
+}
 
+impl<'a, K> Token<'a, K> {
     pub fn value(&self) -> &str {
-        self.value
+        &self.value
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub fn leading(&self) -> &[Token<'a, K>] {
+        &self.leading
+    }
+
+    pub fn trailing(&self) -> &[Token<'a, K>] {
+        &self.trailing
+    }
+
+    pub fn into_owned(self) -> Token<'static, K> {
+        Token {
+            kind: self.kind,
+            value: Cow::Owned(self.value.into_owned()),
+            span: self.span,
+            leading: self.leading.into_iter().map(Token::into_owned).collect(),
+            trailing: self.trailing.into_iter().map(Token::into_owned).collect(),
+        }
     }
 }
 
-pub struct Lexer(pub &'static [Rule]);
+pub struct Lexer<K: 'static = usize> {
+    pub rules: &'static [Rule<K>],
+    // DFA of each mode, recognizing all rules of that mode at once; accepting state yields index into `rules`.
+    // lexing starts in mode 0.
+    pub modes: &'static [Dfa],
+    // kind of tokens covering unmatched input, on recovery
+    pub error: K,
+}
 
-impl Lexer {
-    pub fn lex_once<'a>(&self, from: &'a str) -> Option<Token<'a>> {
-        let mut token: Option<Token> = None;
-        for rule in self.0 {
-            if let Some(size) = rule.pat.consume(from) {
-                let previous = match &token {
-                    None => 0,
-                    Some(t) => t.value.len()
-                };
+// token at some offset: of rule, or of `Lexer::error` covering unmatched input
+pub(crate) struct Scan<'a, K: 'static> {
+    rule: Option<&'static Rule<K>>,
+    token: Token<'a, K>,
+    error: Option<LexError<K>>,
+}
 
-                if previous < size {
-                    token = Some(Token::new(rule.id, &from[..size]));
-                }
+impl<'a, K: Copy> Scan<'a, K> {
+    pub(crate) fn len(&self) -> usize {
+        self.token.value.len()
+    }
+
+    pub(crate) fn into_owned(self) -> Scan<'static, K> {
+        Scan {
+            rule: self.rule,
+            token: self.token.into_owned(),
+            error: self.error,
+        }
+    }
+}
+
+impl<K: Copy> Lexer<K> {
+    // what `from`, which is at `at` in input, starts with in `mode`.
+    // `None` if input following `from` could change it, unless `last`.
+    pub(crate) fn scan<'a>(&self, mode: usize, from: &'a str, at: usize, last: bool) -> Option<Scan<'a, K>> {
+        let rules: &'static [Rule<K>] = self.rules;
+        let dfa = &self.modes[mode];
+
+        let (found, more) = dfa.scan_more(from);
+        if more && !last {
+            return None;
+        }
+
+        if let Some((rule, size)) = found {
+            let rule = &rules[rule];
+            return Some(Scan { rule: Some(rule), token: Token::new(rule.id, &from[..size], at), error: None });
+        }
+
+        // unmatched until some rule matches
+        let end = match dfa.resync(from) {
+            (_, true) if !last => return None,
+            (Some(end), _) => end,
+            (None, _) if last => from.len(),
+            (None, _) => return None,
+        };
+
+        let error = LexError {
+            cause: Cause::Unmatched,
+            at,
+            span: at..at + end,
+            found: from.chars().next().unwrap(),
+            closest: dfa.closest(from).into_iter().map(|rule| rules[rule].id).collect(),
+        };
+        Some(Scan { rule: None, token: Token::new(self.error, &from[..end], at), error: Some(error) })
+    }
+
+    // longest match in first mode, whatever its channel is; earlier rule on tie
+    pub fn lex_once<'a>(&self, from: &'a str) -> Option<Token<'a, K>> {
+        let scan = self.scan(0, from, 0, true)?;
+        scan.rule.map(|_| scan.token)
+    }
+
+    // tokens for parser; hidden tokens become trivia, and are lost if input has no token for them.
+    // fails on first error.
+    pub fn lex<'a>(&self, from: &'a str) -> Result<Vec<Token<'a, K>>, LexError<K>> {
+        self.tokens(from).collect()
+    }
+
+    // as `lex`, but unmatched input becomes token of kind `error`, and lexing goes on from where some rule matches.
+    // token popping first mode is kept as is, leaving mode unchanged.
+    pub fn lex_recover<'a>(&self, from: &'a str) -> (Vec<Token<'a, K>>, Vec<LexError<K>>) {
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        for item in self.tokens(from) {
+            match item {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
             }
         }
 
-        token
+        (tokens, errors)
+    }
+
+    // tokens one by one, as `lex_recover` makes them; each error comes right before its token
+    pub fn tokens<'l, 'a>(&'l self, from: &'a str) -> Tokens<'l, 'a, K> {
+        Tokens::new(self, from)
+    }
+
+    // as `tokens`, reading input in chunks; only token being lexed, and trivia before it, is kept in memory
+    pub fn stream<R: Read>(&self, reader: R) -> Stream<'_, R, K> {
+        Stream::new(self, reader)
+    }
+}
+
+// mode stack, and tokens being lexed; token is held back until next one, as its trailing trivia is known only then
+pub(crate) struct Cursor<'a, K> {
+    modes: Vec<usize>,
+    trivia: Vec<Token<'a, K>>,
+    pending: Option<Token<'a, K>>,
+    ready: VecDeque<Result<Token<'a, K>, LexError<K>>>,
+}
+
+impl<'a, K: Copy> Cursor<'a, K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            modes: vec![0],
+            trivia: Vec::new(),
+            pending: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn mode(&self) -> usize {
+        *self.modes.last().unwrap()
     }
 
-    pub fn lex<'a>(&self, from: &'a str) -> Result<Vec<Token<'a>>, usize> {
-        let mut tokens: Vec<Token> = Vec::new();
-        let mut offset: usize = 0;
+    pub(crate) fn pop(&mut self) -> Option<Result<Token<'a, K>, LexError<K>>> {
+        self.ready.pop_front()
+    }
+
+    pub(crate) fn push(&mut self, scan: Scan<'a, K>) {
+        let Scan { rule, mut token, mut error } = scan;
+
+        let mut channel = Channel::Token;
+        if let Some(rule) = rule {
+            channel = rule.channel;
+            match rule.mode {
+                None => {}
+                Some(Mode::Push(mode)) => self.modes.push(mode),
+                Some(Mode::Pop) if self.modes.len() > 1 => {
+                    self.modes.pop();
+                }
+                Some(Mode::Pop) => {
+                    error = Some(LexError {
+                        cause: Cause::Pop,
+                        at: token.span.start,
+                        span: token.span(),
+                        found: token.value.chars().next().unwrap(),
+                        closest: Vec::new(),
+                    })
+                }
+                Some(Mode::Switch(mode)) => *self.modes.last_mut().unwrap() = mode,
+            }
+        }
+
+        if let Some(error) = error {
+            self.ready.extend(self.pending.take().map(Ok));
+            self.ready.push_back(Err(error));
+        }
 
-        while offset < from.len() {
-            let token = match self.lex_once(&from[offset..]) {
-                None => return Err(offset),
-                Some(token) => token,
-            };
+        match channel {
+            Channel::Token => {
+                token.leading = std::mem::take(&mut self.trivia);
+                self.ready.extend(self.pending.replace(token).map(Ok));
+            }
+            Channel::Skip => {}
+            Channel::Hidden => self.trivia.push(token),
+        }
+    }
 
-            offset += token.value.len();
-            tokens.push(token);
+    // at end of input
+    pub(crate) fn finish(&mut self) {
+        if let Some(mut last) = self.pending.take() {
+            last.trailing = std::mem::take(&mut self.trivia);
+            self.ready.push_back(Ok(last));
         }
-        
-        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::Transition;
+
+    // 'a', skip ' ', hidden '#', '"' entering string mode, ')' leaving mode, 'if'
+    // string mode: [^"]+, '"' leaving it
+    static LEXER: Lexer = Lexer {
+        rules: &[
+            Rule { id: 0, channel: Channel::Token, mode: None },
+            Rule { id: 1, channel: Channel::Skip, mode: None },
+            Rule { id: 2, channel: Channel::Hidden, mode: None },
+            Rule { id: 3, channel: Channel::Token, mode: Some(Mode::Push(1)) },
+            Rule { id: 4, channel: Channel::Token, mode: Some(Mode::Pop) },
+            Rule { id: 5, channel: Channel::Token, mode: None },
+            Rule { id: 6, channel: Channel::Token, mode: Some(Mode::Pop) },
+            Rule { id: 7, channel: Channel::Token, mode: None },
+        ],
+        modes: &[
+            Dfa {
+                offsets: &[0, 6, 6, 6, 6, 6, 6, 7, 7],
+                transitions: &[
+                    Transition { range: ' '..=' ', target: 1 },
+                    Transition { range: '"'..='"', target: 4 },
+                    Transition { range: '#'..='#', target: 2 },
+                    Transition { range: ')'..=')', target: 5 },
+                    Transition { range: 'a'..='a', target: 3 },
+                    Transition { range: 'i'..='i', target: 6 },
+                    Transition { range: 'f'..='f', target: 7 },
+                ],
+                accept: &[None, Some(1), Some(2), Some(0), Some(3), Some(6), None, Some(7)],
+            },
+            Dfa {
+                offsets: &[0, 3, 5, 5],
+                transitions: &[
+                    Transition { range: '\0'..='!', target: 1 },
+                    Transition { range: '"'..='"', target: 2 },
+                    Transition { range: '#'..=char::MAX, target: 1 },
+                    Transition { range: '\0'..='!', target: 1 },
+                    Transition { range: '#'..=char::MAX, target: 1 },
+                ],
+                accept: &[None, Some(5), Some(4)],
+            },
+        ],
+        error: 8,
+    };
+
+    // [a-z]* 'z', which must give back last char matched by [a-z]*
+    static ENDS_Z: Lexer = Lexer {
+        rules: &[Rule { id: 0, channel: Channel::Token, mode: None }],
+        modes: &[Dfa {
+            offsets: &[0, 2, 4],
+            transitions: &[
+                Transition { range: 'a'..='y', target: 0 },
+                Transition { range: 'z'..='z', target: 1 },
+                Transition { range: 'a'..='y', target: 0 },
+                Transition { range: 'z'..='z', target: 1 },
+            ],
+            accept: &[None, Some(0)],
+        }],
+        error: 1,
+    };
+
+    // ('a' | 'ab') 'c', which must go on from either alternative
+    static OR: Lexer = Lexer {
+        rules: &[Rule { id: 0, channel: Channel::Token, mode: None }],
+        modes: &[Dfa {
+            offsets: &[0, 1, 3, 4, 4],
+            transitions: &[
+                Transition { range: 'a'..='a', target: 1 },
+                Transition { range: 'b'..='b', target: 2 },
+                Transition { range: 'c'..='c', target: 3 },
+                Transition { range: 'c'..='c', target: 3 },
+            ],
+            accept: &[None, None, None, Some(0)],
+        }],
+        error: 1,
+    };
+
+    #[test]
+    fn test() {
+        let kinds = |tokens: &[Token]| tokens.iter().map(Token::kind).collect::<Vec<_>>();
+
+        let tokens = LEXER.lex("# a ##a #").unwrap();
+        assert_eq!(kinds(&tokens), [0, 0]);
+        assert_eq!(kinds(tokens[0].leading()), [2]);
+        assert_eq!(kinds(tokens[1].leading()), [2, 2]);
+        assert_eq!(kinds(tokens[1].trailing()), [2]);
+        assert!(tokens[0].trailing().is_empty());
+
+        assert_eq!(LEXER.lex_once(" a").unwrap().kind(), 1);
+        let error = LEXER.lex("a b").err().unwrap();
+        assert_eq!((error.cause, error.at, error.span.clone(), error.found), (Cause::Unmatched, 2, 2..3, 'b'));
+        assert!(error.closest.is_empty());
+        assert_eq!(error.to_string(), "unexpected character 'b'");
+
+        // skip and hidden rules of first mode do not apply in string mode
+        let tokens = LEXER.lex("a\"x a#\" #a").unwrap();
+        assert_eq!(kinds(&tokens), [0, 3, 5, 4, 0]);
+        assert_eq!(tokens[2].value(), "x a#");
+        assert_eq!(tokens[2].span(), 2..6);
+        assert_eq!(tokens[4].leading()[0].span(), 8..9);
+        assert_eq!(tokens[4].span(), 9..10);
+        assert_eq!(kinds(tokens[4].leading()), [2]);
+
+        assert_eq!(LEXER.lex("a\"").unwrap().len(), 2);
+        let error = LEXER.lex("a)").err().unwrap();
+        assert_eq!((error.cause, error.at, error.span), (Cause::Pop, 1, 1..2));
+
+        // 'i' leads only to 'if'; error spans until ' ' matches
+        let error = LEXER.lex("a ixy a").err().unwrap();
+        assert_eq!((error.at, error.span.clone(), error.found), (2, 2..5, 'i'));
+        assert_eq!(error.closest, [7]);
+        assert_eq!(error.to_string(), "unexpected character 'i' (closest: 7)");
+
+        let (tokens, errors) = LEXER.lex_recover("#ixy a) bb a\"x");
+        assert_eq!(kinds(&tokens), [8, 0, 6, 8, 0, 3, 5]);
+        assert_eq!(tokens[0].value(), "ixy");
+        assert_eq!(kinds(tokens[0].leading()), [2]);
+        assert_eq!(tokens[3].value(), "bb");
+        let errors = errors.iter().map(|error| (error.cause, error.span.clone())).collect::<Vec<_>>();
+        assert_eq!(errors, [(Cause::Unmatched, 1..4), (Cause::Pop, 6..7), (Cause::Unmatched, 8..10)]);
+
+        // runs from every offset at once: 'if' at 2 is found while one from 1 is still going
+        let (tokens, errors) = LEXER.lex_recover("iiif");
+        assert_eq!(errors[0].span, 0..2);
+        assert_eq!(tokens.iter().map(|token| token.span()).collect::<Vec<_>>(), [0..2, 2..4]);
+
+        // in one pass over garbage
+        let garbage = "b".repeat(1 << 20) + " a";
+        let error = LEXER.lex(&garbage).err().unwrap();
+        assert_eq!(error.span, 0..1 << 20);
+
+        // patterns are lexed by DFA, so there is no greedy walk to backtrack
+        let spans = |tokens: Vec<Token>| {
+            tokens.iter().map(|token| (token.span().start, token.span().end)).collect::<Vec<_>>()
+        };
+        assert_eq!(spans(ENDS_Z.lex("abz").unwrap()), [(0, 3)]);
+        assert_eq!(spans(ENDS_Z.lex("zzaz").unwrap()), [(0, 4)]);
+        assert_eq!(spans(OR.lex("abc").unwrap()), [(0, 3)]);
+        assert_eq!(spans(OR.lex("acabc").unwrap()), [(0, 2), (2, 5)]);
+        assert_eq!(OR.lex("ab").err().unwrap().cause, Cause::Unmatched);
     }
 }
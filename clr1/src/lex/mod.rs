@@ -1,6 +1,12 @@
-mod pattern;
+#[allow(clippy::module_inception)]
 mod lex;
-mod consume;
+mod dfa;
+mod error;
+mod offset;
+mod stream;
 
-pub use pattern::*;
 pub use lex::*;
+pub use dfa::*;
+pub use error::*;
+pub use offset::*;
+pub use stream::*;
@@ -0,0 +1,74 @@
+use std::fmt::{Display, Formatter};
+
+// zero-based line and column (in chars, not counting '\r'); shown one-based
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Offset(usize, usize);
+
+impl Offset {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self(line, column)
+    }
+
+    pub fn line(&self) -> usize {
+        self.0
+    }
+
+    pub fn column(&self) -> usize {
+        self.1
+    }
+}
+
+impl Display for Offset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({},{})", self.0 + 1, self.1 + 1)
+    }
+}
+
+// start of each line of input, to turn byte offsets into line and column
+pub struct Lines<'a> {
+    from: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(from: &'a str) -> Self {
+        let starts = std::iter::once(0).chain(from.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self { from, starts }
+    }
+
+    // offset past end is clamped to end; offset inside char counts as that char
+    pub fn offset(&self, at: usize) -> Offset {
+        let at = at.min(self.from.len());
+        let line = self.starts.partition_point(|&start| start <= at) - 1;
+
+        let start = self.starts[line];
+        let column = self.from[start..]
+            .char_indices()
+            .take_while(|&(i, _)| start + i < at)
+            .filter(|&(_, ch)| ch != '\r')
+            .count();
+
+        Offset(line, column)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let lines = Lines::new("ab\r\n한글\n\nx");
+
+        assert_eq!(lines.offset(0), Offset::new(0, 0));
+        assert_eq!(lines.offset(1), Offset::new(0, 1));
+        assert_eq!(lines.offset(3), Offset::new(0, 2));
+        assert_eq!(lines.offset(4), Offset::new(1, 0));
+        assert_eq!(lines.offset(7), Offset::new(1, 1));
+        assert_eq!(lines.offset(10), Offset::new(1, 2));
+        assert_eq!(lines.offset(11), Offset::new(2, 0));
+        assert_eq!(lines.offset(12), Offset::new(3, 0));
+        assert_eq!(lines.offset(100), Offset::new(3, 1));
+        assert_eq!(lines.offset(12).to_string(), "(4,1)");
+    }
+}
@@ -0,0 +1,259 @@
+use crate::lex::error::LexError;
+use crate::lex::lex::{Cursor, Lexer, Token};
+use std::fmt::{Debug, Display, Formatter};
+use std::io::{ErrorKind, Read};
+
+// bytes read at once
+const CHUNK: usize = 8192;
+
+pub struct Tokens<'l, 'a, K: 'static> {
+    lexer: &'l Lexer<K>,
+    from: &'a str,
+    offset: usize,
+    cursor: Cursor<'a, K>,
+}
+
+impl<'l, 'a, K: Copy> Tokens<'l, 'a, K> {
+    pub(crate) fn new(lexer: &'l Lexer<K>, from: &'a str) -> Self {
+        Self {
+            lexer,
+            from,
+            offset: 0,
+            cursor: Cursor::new(),
+        }
+    }
+}
+
+impl<'a, K: Copy> Iterator for Tokens<'_, 'a, K> {
+    type Item = Result<Token<'a, K>, LexError<K>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.cursor.pop() {
+                return Some(item);
+            }
+
+            if self.offset == self.from.len() {
+                self.cursor.finish();
+                return self.cursor.pop();
+            }
+
+            let scan = self.lexer.scan(self.cursor.mode(), &self.from[self.offset..], self.offset, true).unwrap();
+            self.offset += scan.len();
+            self.cursor.push(scan);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError<K = usize> {
+    Io(std::io::Error),
+    Lex(LexError<K>),
+}
+
+impl<K: Display> Display for ReadError<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Lex(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl<K: Debug + Display> std::error::Error for ReadError<K> {}
+
+impl<K> From<std::io::Error> for ReadError<K> {
+    fn from(e: std::io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+// tokens own their values, as buffer is reused; spans are offsets in whole input
+pub struct Stream<'l, R, K: 'static> {
+    lexer: &'l Lexer<K>,
+    reader: R,
+    // unlexed input from `at`, possibly ending in middle of token or char
+    buffer: Vec<u8>,
+    at: usize,
+    // bytes of input dropped from front of `buffer`
+    base: usize,
+    eof: bool,
+    // set at end of input, or on I/O error or invalid UTF-8, after which nothing more is read
+    done: bool,
+    // given after tokens before it
+    error: Option<std::io::Error>,
+    cursor: Cursor<'static, K>,
+}
+
+impl<'l, R: Read, K: Copy> Stream<'l, R, K> {
+    pub(crate) fn new(lexer: &'l Lexer<K>, reader: R) -> Self {
+        Self {
+            lexer,
+            reader,
+            buffer: Vec::new(),
+            at: 0,
+            base: 0,
+            eof: false,
+            done: false,
+            error: None,
+            cursor: Cursor::new(),
+        }
+    }
+
+    // drops lexed input, then reads another chunk
+    fn fill(&mut self) -> std::io::Result<()> {
+        self.buffer.drain(..self.at);
+        self.base += self.at;
+        self.at = 0;
+
+        let len = self.buffer.len();
+        self.buffer.resize(len + CHUNK, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buffer[len..]) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                read => break read,
+            }
+        };
+
+        match read {
+            Ok(read) => {
+                self.buffer.truncate(len + read);
+                self.eof = read == 0;
+                Ok(())
+            }
+            Err(e) => {
+                self.buffer.truncate(len);
+                Err(e)
+            }
+        }
+    }
+
+    fn step(&mut self) -> std::io::Result<()> {
+        let rest = &self.buffer[self.at..];
+        // whether more input may follow valid part
+        let (valid, more) = match std::str::from_utf8(rest) {
+            Ok(_) => (rest.len(), !self.eof),
+            // char cut at end of buffer may be completed by next chunk
+            Err(e) => (e.valid_up_to(), e.error_len().is_none() && !self.eof),
+        };
+
+        if valid == 0 {
+            if !more {
+                return Err(std::io::Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"));
+            }
+            self.fill()?;
+            return Ok(());
+        }
+
+        let from = std::str::from_utf8(&rest[..valid]).unwrap();
+        match self.lexer.scan(self.cursor.mode(), from, self.base + self.at, !more) {
+            Some(scan) => {
+                self.at += scan.len();
+                self.cursor.push(scan.into_owned());
+            }
+            None => self.fill()?,
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read, K: Copy> Iterator for Stream<'_, R, K> {
+    type Item = Result<Token<'static, K>, ReadError<K>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.cursor.pop() {
+                return Some(item.map_err(ReadError::Lex));
+            }
+
+            if self.done {
+                return self.error.take().map(|e| Err(e.into()));
+            }
+
+            if self.eof && self.at == self.buffer.len() {
+                self.done = true;
+                self.cursor.finish();
+                continue;
+            }
+
+            if let Err(e) = self.step() {
+                self.done = true;
+                self.error = Some(e);
+                self.cursor.finish();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::{Cause, Channel, Dfa, Mode, Rule, Transition};
+
+    // [a-zé]+, skip ' ', ')' leaving mode
+    static LEXER: Lexer = Lexer {
+        rules: &[
+            Rule { id: 0, channel: Channel::Token, mode: None },
+            Rule { id: 1, channel: Channel::Skip, mode: None },
+            Rule { id: 2, channel: Channel::Token, mode: Some(Mode::Pop) },
+        ],
+        modes: &[Dfa {
+            offsets: &[0, 4, 4, 6, 6],
+            transitions: &[
+                Transition { range: ' '..=' ', target: 1 },
+                Transition { range: ')'..=')', target: 3 },
+                Transition { range: 'a'..='z', target: 2 },
+                Transition { range: 'é'..='é', target: 2 },
+                Transition { range: 'a'..='z', target: 2 },
+                Transition { range: 'é'..='é', target: 2 },
+            ],
+            accept: &[None, Some(1), Some(0), Some(2)],
+        }],
+        error: 3,
+    };
+
+    // byte at a time, so every token and char straddles reads
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Read for Bytes<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test() {
+        let from = "ab éé##zz ) é";
+        let expected = LEXER
+            .tokens(from)
+            .map(|item| item.map(|token| (token.kind(), token.value().to_string(), token.span())))
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 8);
+        assert_eq!(expected[3], Ok((3, "##".to_string(), 7..9)));
+
+        let tokens = LEXER
+            .stream(Bytes(from.as_bytes()))
+            .map(|item| match item {
+                Ok(token) => Ok((token.kind(), token.value().to_string(), token.span())),
+                Err(ReadError::Lex(e)) => Err(e),
+                Err(ReadError::Io(e)) => panic!("{}", e),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, expected);
+
+        let errors = tokens.iter().filter_map(|item| item.as_ref().err()).map(|e| e.cause).collect::<Vec<_>>();
+        assert_eq!(errors, [Cause::Unmatched, Cause::Pop]);
+
+        // 'a' is lexed before invalid byte is reached
+        let mut stream = LEXER.stream(Bytes(b"a \xff"));
+        assert_eq!(stream.next().unwrap().unwrap().value(), "a");
+        assert!(matches!(stream.next(), Some(Err(ReadError::Io(e))) if e.kind() == ErrorKind::InvalidData));
+        assert!(stream.next().is_none());
+    }
+}
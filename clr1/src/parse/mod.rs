@@ -0,0 +1,10 @@
+#[allow(clippy::module_inception)]
+mod parse;
+mod reduce;
+mod table;
+mod tree;
+
+pub use parse::*;
+pub use reduce::*;
+pub use table::*;
+pub use tree::*;
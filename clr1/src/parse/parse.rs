@@ -0,0 +1,434 @@
+use crate::lex::Token;
+use crate::parse::reduce::{Reducer, Values};
+use crate::parse::table::{Action, Nonterminal, Production, Table};
+use crate::parse::tree::{Builder, Tree};
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::Range;
+
+pub struct Parser<K: 'static = usize> {
+    // token kind of each terminal, `None` for end of input
+    pub terminals: &'static [Option<K>],
+    // terminal of each token kind, by kind as `usize`; `None` if grammar does not use it
+    pub columns: &'static [Option<usize>],
+    // first is augmented start symbol
+    pub nonterminals: &'static [Nonterminal],
+    pub productions: &'static [Production],
+    // names in `@name`
+    pub captures: &'static [&'static str],
+    // terminal of `error`, if grammar has one, which parser shifts to recover from errors
+    pub error: Option<usize>,
+    // [state][terminal]; error may be detected only after default reductions
+    pub action: Table<Action>,
+    // [state][nonterminal]
+    pub goto: Table<Option<usize>>,
+}
+
+#[derive(Debug)]
+pub struct Error<K = usize> {
+    // index of unexpected token; number of tokens if input ended unexpectedly
+    pub at: usize,
+    // kind of unexpected token, `None` for end of input
+    pub found: Option<K>,
+    // of unexpected token; empty at end of last token if input ended
+    pub span: Range<usize>,
+    // state parser was in
+    pub state: usize,
+    // token kinds acceptable at that point, `None` for end of input
+    pub expected: Vec<Option<K>>,
+}
+
+impl<K: Debug> Display for Error<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = |kind: &Option<K>| kind.as_ref().map_or("end of input".to_string(), |kind| format!("{:?}", kind));
+        let expected = self.expected.iter().map(kind).collect::<Vec<_>>();
+        write!(f, "unexpected {} at {:?} (expected: {})", kind(&self.found), self.span, expected.join(", "))
+    }
+}
+
+impl<K: Debug> std::error::Error for Error<K> {}
+
+// what parser makes of input, as it shifts tokens and reduces productions
+pub(crate) trait Sink<'a, K> {
+    fn shift(&mut self, token: Token<'a, K>);
+
+    // symbols of states popped to recover from error
+    fn discard(&mut self, len: usize);
+
+    fn reduce(&mut self, production: usize);
+}
+
+// productions in order of reduction
+impl<'a, K> Sink<'a, K> for Vec<usize> {
+    fn shift(&mut self, _: Token<'a, K>) {}
+
+    fn discard(&mut self, _: usize) {}
+
+    fn reduce(&mut self, production: usize) {
+        self.push(production);
+    }
+}
+
+impl<K: Copy + Eq + Into<usize>> Parser<K> {
+    // end of input is always first terminal
+    fn terminal(&self, kind: Option<K>) -> Option<usize> {
+        match kind {
+            None => Some(0),
+            Some(kind) => self.columns.get(kind.into()).copied().flatten(),
+        }
+    }
+
+    fn expected(&self, state: usize) -> Vec<Option<K>> {
+        (0..self.terminals.len())
+            .filter(|&terminal| Some(terminal) != self.error && self.action.get(state, terminal) != Action::Error)
+            .map(|terminal| self.terminals[terminal])
+            .collect()
+    }
+
+    // returns productions in order of reduction (reversed rightmost derivation)
+    pub fn parse<'a>(&self, tokens: impl IntoIterator<Item = Token<'a, K>>) -> Result<Vec<usize>, Error<K>> {
+        let mut reductions = Vec::new();
+        self.run(tokens, &mut reductions)?;
+        Ok(reductions)
+    }
+
+    // lossless tree of tokens, trivia included
+    pub fn parse_tree<'a>(&self, tokens: impl IntoIterator<Item = Token<'a, K>>) -> Result<Tree<'a, K>, Error<K>> {
+        let mut builder = Builder::new(self);
+        self.run(tokens, &mut builder)?;
+        Ok(builder.finish())
+    }
+
+    // calls `reduce` with index and values of each production of rule as it is reduced, bottom-up;
+    // returns what start symbol was reduced to
+    pub fn parse_with<'a, V>(
+        &self,
+        tokens: impl IntoIterator<Item = Token<'a, K>>,
+        reduce: impl FnMut(usize, Values<'a, K, V>) -> V,
+    ) -> Result<V, Error<K>> {
+        let mut reducer = Reducer::new(self, reduce);
+        self.run(tokens, &mut reducer)?;
+        Ok(reducer.finish())
+    }
+
+    // tree, unless some error could not be recovered from, and all errors
+    pub fn parse_tree_recover<'a>(
+        &self,
+        tokens: impl IntoIterator<Item = Token<'a, K>>,
+    ) -> (Option<Tree<'a, K>>, Vec<Error<K>>) {
+        let mut builder = Builder::new(self);
+        let (accepted, errors) = self.drive(tokens, &mut builder, true);
+        (accepted.then(|| builder.finish()), errors)
+    }
+
+    // `parse_with`, recovering from errors as `parse_tree_recover` does
+    pub fn parse_with_recover<'a, V>(
+        &self,
+        tokens: impl IntoIterator<Item = Token<'a, K>>,
+        reduce: impl FnMut(usize, Values<'a, K, V>) -> V,
+    ) -> (Option<V>, Vec<Error<K>>) {
+        let mut reducer = Reducer::new(self, reduce);
+        let (accepted, errors) = self.drive(tokens, &mut reducer, true);
+        (accepted.then(|| reducer.finish()), errors)
+    }
+
+    // fails on first error
+    fn run<'a>(
+        &self,
+        tokens: impl IntoIterator<Item = Token<'a, K>>,
+        sink: &mut impl Sink<'a, K>,
+    ) -> Result<(), Error<K>> {
+        let (_, mut errors) = self.drive(tokens, sink, false);
+        errors.pop().map_or(Ok(()), Err)
+    }
+
+    // whether input is accepted, and errors. with `recover` and `error` in grammar,
+    // it recovers from error as yacc does: pops states until one shifts `error`, shifts it,
+    // then discards tokens until one can follow.
+    // errors before 3 tokens are shifted after that are not reported, as they are likely to be caused by the first one
+    fn drive<'a>(
+        &self,
+        tokens: impl IntoIterator<Item = Token<'a, K>>,
+        sink: &mut impl Sink<'a, K>,
+        recover: bool,
+    ) -> (bool, Vec<Error<K>>) {
+        let mut tokens = tokens.into_iter();
+        let mut lookahead = tokens.next();
+        let mut at = 0;
+        // end of last token taken, where input ended once it has
+        let mut end = 0;
+
+        let mut errors = Vec::new();
+        // tokens shifted since `error`, while recovering
+        let mut recovering = None;
+
+        let mut stack = vec![0];
+        loop {
+            let state = *stack.last().unwrap();
+
+            let action = match self.terminal(lookahead.as_ref().map(|token| token.kind())) {
+                None => Action::Error,
+                Some(terminal) => self.action.get(state, terminal),
+            };
+
+            match action {
+                Action::Shift(next) => {
+                    stack.push(next);
+                    // only end of input has no token, and it is never shifted
+                    let token = std::mem::replace(&mut lookahead, tokens.next()).unwrap();
+                    end = token.span().end;
+                    sink.shift(token);
+                    at += 1;
+
+                    recovering = recovering.map(|shifted| shifted + 1).filter(|&shifted| shifted < 3);
+                }
+                Action::Reduce(production) => {
+                    let Production { head, len, .. } = self.productions[production];
+                    stack.truncate(stack.len() - len);
+
+                    let top = *stack.last().unwrap();
+                    let next = self.goto.get(top, head).expect("GOTO entry missing for reduced production");
+                    stack.push(next);
+
+                    sink.reduce(production);
+                }
+                Action::Accept => break (true, errors),
+                Action::Error => {
+                    if recovering.is_none() {
+                        errors.push(Error {
+                            at,
+                            found: lookahead.as_ref().map(|token| token.kind()),
+                            span: lookahead.as_ref().map_or(end..end, |token| token.span()),
+                            state,
+                            expected: self.expected(state),
+                        });
+                    }
+
+                    let Some(error) = self.error.filter(|_| recover) else {
+                        break (false, errors);
+                    };
+
+                    // token cannot follow `error` just shifted
+                    if recovering == Some(0) {
+                        let Some(token) = lookahead.take() else {
+                            break (false, errors);
+                        };
+                        end = token.span().end;
+                        lookahead = tokens.next();
+                        at += 1;
+                        continue;
+                    }
+
+                    let shifts = |state: usize| match self.action.get(state, error) {
+                        Action::Shift(next) => Some(next),
+                        _ => None,
+                    };
+                    let Some(depth) = stack.iter().rposition(|&state| shifts(state).is_some()) else {
+                        break (false, errors);
+                    };
+                    sink.discard(stack.len() - 1 - depth);
+                    stack.truncate(depth + 1);
+
+                    stack.push(shifts(stack[depth]).unwrap());
+                    let at = lookahead.as_ref().map_or(end, |token| token.span().start);
+                    sink.shift(Token::new(self.terminals[error].unwrap(), "", at));
+                    recovering = Some(0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::{Channel, Dfa, Lexer, Rule, Transition};
+    use crate::parse::Nonterminal;
+
+    // S : 'a' S | 'b' ;
+    static LEXER: Lexer = Lexer {
+        rules: &[
+            Rule { id: 0, channel: Channel::Token, mode: None },
+            Rule { id: 1, channel: Channel::Token, mode: None },
+        ],
+        modes: &[Dfa {
+            offsets: &[0, 2, 2, 2],
+            transitions: &[
+                Transition { range: 'a'..='a', target: 1 },
+                Transition { range: 'b'..='b', target: 2 },
+            ],
+            accept: &[None, Some(0), Some(1)],
+        }],
+        error: 2,
+    };
+
+    static PARSER: Parser = Parser {
+        terminals: &[None, Some(0), Some(1)],
+        columns: &[Some(1), Some(2)],
+        nonterminals: &[Nonterminal { name: "$accept", helper: false }, Nonterminal { name: "S", helper: false }],
+        productions: &[
+            Production { head: 0, len: 1, captures: &[None] },
+            Production { head: 1, len: 2, captures: &[None, None] },
+            Production { head: 1, len: 1, captures: &[None] },
+        ],
+        captures: &[],
+        error: None,
+        // state 3 and 4 reduce by default
+        action: Table {
+            rows: &[0, 1, 0, 2, 3],
+            base: &[1, 0, 0, 0],
+            default: &[Action::Error, Action::Error, Action::Reduce(2), Action::Reduce(1)],
+            check: &[1, 4, 0, 0],
+            entries: &[Action::Accept, Action::Error, Action::Shift(2), Action::Shift(3)],
+        },
+        goto: Table {
+            rows: &[0, 1, 2, 1, 1],
+            base: &[0, 0, 0],
+            default: &[Some(1), None, Some(4)],
+            check: &[],
+            entries: &[],
+        },
+    };
+
+    // S : T* ; T : A@a Semi | error Semi ; hidden WS : ' '+ ;
+    static RECOVER_LEXER: Lexer = Lexer {
+        rules: &[
+            Rule { id: 0, channel: Channel::Token, mode: None },
+            Rule { id: 1, channel: Channel::Token, mode: None },
+            Rule { id: 2, channel: Channel::Hidden, mode: None },
+        ],
+        modes: &[Dfa {
+            offsets: &[0, 3, 3, 3, 3],
+            transitions: &[
+                Transition { range: ' '..=' ', target: 1 },
+                Transition { range: ';'..=';', target: 2 },
+                Transition { range: 'a'..='a', target: 3 },
+            ],
+            accept: &[None, Some(2), Some(1), Some(0)],
+        }],
+        error: 3,
+    };
+
+    static RECOVER_PARSER: Parser = Parser {
+        terminals: &[None, Some(0), Some(1), Some(3)],
+        columns: &[Some(1), Some(2), None, Some(3)],
+        nonterminals: &[
+            Nonterminal { name: "$accept", helper: false },
+            Nonterminal { name: "S", helper: false },
+            Nonterminal { name: "T", helper: false },
+            Nonterminal { name: "S#1", helper: true },
+        ],
+        productions: &[
+            Production { head: 0, len: 1, captures: &[None] },
+            Production { head: 3, len: 2, captures: &[None, None] },
+            Production { head: 3, len: 0, captures: &[] },
+            Production { head: 1, len: 1, captures: &[None] },
+            Production { head: 2, len: 2, captures: &[Some(0), None] },
+            Production { head: 2, len: 2, captures: &[None, None] },
+        ],
+        captures: &["a"],
+        error: Some(3),
+        // state 2 shifts `error`, so it has no default reduction
+        action: Table {
+            rows: &[0, 1, 2, 3, 4, 5, 6, 7],
+            base: &[0, 2, 0, 2, 3, 0, 0, 0],
+            default: &[
+                Action::Reduce(2),
+                Action::Error,
+                Action::Error,
+                Action::Error,
+                Action::Error,
+                Action::Reduce(1),
+                Action::Reduce(4),
+                Action::Reduce(5),
+            ],
+            check: &[2, 2, 1, 2, 3, 4],
+            entries: &[
+                Action::Reduce(3),
+                Action::Shift(3),
+                Action::Accept,
+                Action::Shift(4),
+                Action::Shift(6),
+                Action::Shift(7),
+            ],
+        },
+        goto: Table {
+            rows: &[0, 1, 2, 1, 1, 1, 1, 1],
+            base: &[0, 0, 0],
+            default: &[Some(1), None, Some(5)],
+            check: &[3, 3, 3, 0],
+            entries: &[Some(1), Some(1), Some(1), Some(2)],
+        },
+    };
+
+    #[test]
+    fn recover() {
+        // second `a` is unexpected, and so is `;` after it, but only first is reported
+        let from = "a; a a; ; a;";
+        let (tree, errors) = RECOVER_PARSER.parse_tree_recover(RECOVER_LEXER.lex(from).unwrap());
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].at, errors[0].state, errors[0].expected.as_slice()), (3, 3, &[Some(1)][..]));
+        assert_eq!((errors[0].found, errors[0].span.clone()), (Some(0), 5..6));
+
+        let tree = tree.unwrap();
+        let productions = tree.root().children().map(|child| child.production().unwrap()).collect::<Vec<_>>();
+        assert_eq!(productions, [4, 5, 5, 4]);
+        let error = tree.root().children().nth(1).unwrap().children().next().unwrap();
+        assert_eq!(error.token().map(|token| (token.kind(), token.span())), Some((3, 5..5)));
+        // discarded tokens are not in tree
+        assert_eq!(tree.root().text(), "a;; ; a;");
+
+        let error = RECOVER_PARSER.parse_tree(RECOVER_LEXER.lex(from).unwrap()).err().unwrap();
+        assert_eq!(error.at, 3);
+
+        let count = |production, values: Values<usize, usize>| match production {
+            3 => values.len(),
+            _ => 1,
+        };
+        let (count, errors) = RECOVER_PARSER.parse_with_recover(RECOVER_LEXER.lex(from).unwrap(), count);
+        assert_eq!((count, errors.len()), (Some(4), 1));
+
+        // end of input cannot follow `error`, nor is there any state to pop to without `error`
+        let (tree, errors) = RECOVER_PARSER.parse_tree_recover(RECOVER_LEXER.lex("a; a").unwrap());
+        assert!(tree.is_none());
+        assert_eq!(errors.iter().map(|error| error.at).collect::<Vec<_>>(), [3]);
+        assert_eq!(errors[0].span, 4..4);
+        let (tree, errors) = PARSER.parse_tree_recover(LEXER.lex("aba").unwrap());
+        assert!(tree.is_none());
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test() {
+        let tokens = LEXER.lex("aab").unwrap();
+        assert_eq!(PARSER.parse(tokens).unwrap(), [2, 1, 1]);
+
+        // length of `S`
+        let tokens = LEXER.lex("aab").unwrap();
+        let len = PARSER.parse_with(tokens, |production, values| match production {
+            1 => {
+                let mut values = values.into_iter().map(|(capture, value)| {
+                    assert!(capture.is_none());
+                    value
+                });
+                assert_eq!(values.next().unwrap().token().value(), "a");
+                values.next().unwrap().reduced() + 1
+            }
+            _ => 1,
+        });
+        assert_eq!(len.unwrap(), 3);
+
+        let tokens = LEXER.lex("aa").unwrap();
+        let error = PARSER.parse(tokens).unwrap_err();
+        assert_eq!(error.at, 2);
+        assert_eq!((error.found, error.span.clone()), (None, 2..2));
+        assert_eq!(error.expected, [Some(0), Some(1)]);
+        assert_eq!(error.to_string(), "unexpected end of input at 2..2 (expected: 0, 1)");
+
+        let tokens = LEXER.lex("aba").unwrap();
+        let error = PARSER.parse(tokens).unwrap_err();
+        assert_eq!(error.at, 2);
+        assert_eq!((error.found, error.span.clone()), (Some(0), 2..3));
+        assert_eq!(error.expected, [None]);
+        assert_eq!(error.to_string(), "unexpected 0 at 2..3 (expected: end of input)");
+    }
+}
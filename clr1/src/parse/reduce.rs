@@ -0,0 +1,88 @@
+use crate::lex::Token;
+use crate::parse::parse::{Parser, Sink};
+use crate::parse::table::Production;
+
+// value of symbol of reduced production
+pub enum Value<'a, K, V> {
+    Token(Token<'a, K>),
+    // what production of rule was reduced to
+    Reduced(V),
+}
+
+impl<'a, K, V> Value<'a, K, V> {
+    pub fn token(self) -> Token<'a, K> {
+        match self {
+            Value::Token(token) => token,
+            Value::Reduced(_) => panic!("value is not token"),
+        }
+    }
+
+    pub fn reduced(self) -> V {
+        match self {
+            Value::Token(_) => panic!("value is token"),
+            Value::Reduced(value) => value,
+        }
+    }
+}
+
+// values of reduced production with their `@name`, those of helper nonterminals spliced in as in `Tree`
+pub type Values<'a, K, V> = Vec<(Option<&'static str>, Value<'a, K, V>)>;
+
+// values symbols of parser stack stand for
+enum Entry<'a, K, V> {
+    One(Value<'a, K, V>),
+    Spliced(Values<'a, K, V>),
+}
+
+// calls `reduce` on reduction of each production of rule
+pub(crate) struct Reducer<'p, 'a, K: 'static, V, F> {
+    parser: &'p Parser<K>,
+    stack: Vec<Entry<'a, K, V>>,
+    reduce: F,
+}
+
+impl<'p, 'a, K, V, F> Reducer<'p, 'a, K, V, F> {
+    pub(crate) fn new(parser: &'p Parser<K>, reduce: F) -> Self {
+        Self {
+            parser,
+            stack: Vec::new(),
+            reduce,
+        }
+    }
+
+    // on accept, start symbol is only one left
+    pub(crate) fn finish(mut self) -> V {
+        let Some(Entry::One(Value::Reduced(value))) = self.stack.pop() else {
+            unreachable!("start symbol is not helper");
+        };
+        value
+    }
+}
+
+impl<'a, K, V, F: FnMut(usize, Values<'a, K, V>) -> V> Sink<'a, K> for Reducer<'_, 'a, K, V, F> {
+    fn shift(&mut self, token: Token<'a, K>) {
+        self.stack.push(Entry::One(Value::Token(token)));
+    }
+
+    fn discard(&mut self, len: usize) {
+        self.stack.truncate(self.stack.len() - len);
+    }
+
+    fn reduce(&mut self, production: usize) {
+        let Production { head, len, captures } = self.parser.productions[production];
+
+        let mut values = Vec::new();
+        for (entry, &capture) in self.stack.drain(self.stack.len() - len..).zip(captures) {
+            match entry {
+                Entry::One(value) => values.push((capture.map(|capture| self.parser.captures[capture]), value)),
+                Entry::Spliced(spliced) => values.extend(spliced),
+            }
+        }
+
+        let entry = match self.parser.nonterminals[head].helper {
+            true => Entry::Spliced(values),
+            false => Entry::One(Value::Reduced((self.reduce)(production, values))),
+        };
+        self.stack.push(entry);
+    }
+}
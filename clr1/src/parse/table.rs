@@ -0,0 +1,43 @@
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Action {
+    Error,
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+#[derive(Copy, Clone)]
+pub struct Production {
+    pub head: usize,
+    pub len: usize,
+    // index into `Parser::captures` of `@name` each symbol of body is captured as
+    pub captures: &'static [Option<usize>],
+}
+
+pub struct Nonterminal {
+    pub name: &'static str,
+    // synthesized from sub-pattern of rule (`Rule#1`); spliced into node of that rule in tree
+    pub helper: bool,
+}
+
+// row-displaced (comb-vector) table; rows sharing default and entries share one packed row.
+// entry at [row, column] is `entries[base[r] + column]` if `check[base[r] + column] == r`, otherwise `default[r]`,
+// where `r = rows[row]`
+pub struct Table<T: 'static> {
+    pub rows: &'static [usize],
+    pub base: &'static [usize],
+    pub default: &'static [T],
+    pub check: &'static [usize],
+    pub entries: &'static [T],
+}
+
+impl<T: Copy> Table<T> {
+    pub fn get(&self, row: usize, column: usize) -> T {
+        let r = self.rows[row];
+        let slot = self.base[r] + column;
+        match self.check.get(slot) {
+            Some(&c) if c == r => self.entries[slot],
+            _ => self.default[r],
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::lex::Token;
+use crate::parse::parse::{Parser, Sink};
+use crate::parse::table::Production;
+
+enum Data<'a, K> {
+    Node {
+        nonterminal: usize,
+        // which was reduced
+        production: usize,
+        children: Vec<usize>,
+    },
+    Leaf(Token<'a, K>),
+}
+
+struct Element<'a, K> {
+    parent: Option<usize>,
+    // index into `Parser::captures`
+    capture: Option<usize>,
+    data: Data<'a, K>,
+}
+
+// lossless concrete syntax tree, whose leaves are tokens with their trivia.
+// nodes of helper nonterminals are spliced into node of rule they come from, e.g. `S : 'a'* ;` gives `S` node
+// with `'a'` leaves as its children.
+pub struct Tree<'a, K = usize> {
+    elements: Vec<Element<'a, K>>,
+    root: usize,
+    captures: &'static [&'static str],
+}
+
+impl<'a, K> Tree<'a, K> {
+    pub fn root(&self) -> Node<'_, 'a, K> {
+        Node { tree: self, id: self.root }
+    }
+}
+
+// node or leaf of tree
+pub struct Node<'t, 'a, K = usize> {
+    tree: &'t Tree<'a, K>,
+    id: usize,
+}
+
+impl<K> Clone for Node<'_, '_, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K> Copy for Node<'_, '_, K> {}
+
+impl<'t, 'a, K> Node<'t, 'a, K> {
+    fn data(&self) -> &'t Data<'a, K> {
+        &self.tree.elements[self.id].data
+    }
+
+    // `None` for leaf
+    pub fn nonterminal(&self) -> Option<usize> {
+        match self.data() {
+            Data::Node { nonterminal, .. } => Some(*nonterminal),
+            Data::Leaf(_) => None,
+        }
+    }
+
+    // `None` for leaf
+    pub fn production(&self) -> Option<usize> {
+        match self.data() {
+            Data::Node { production, .. } => Some(*production),
+            Data::Leaf(_) => None,
+        }
+    }
+
+    // `None` for node
+    pub fn token(&self) -> Option<&'t Token<'a, K>> {
+        match self.data() {
+            Data::Node { .. } => None,
+            Data::Leaf(token) => Some(token),
+        }
+    }
+
+    // `@name` this was captured as by rule of parent node, even if through `?`, `*` or such
+    pub fn capture(&self) -> Option<&'static str> {
+        let capture = self.tree.elements[self.id].capture?;
+        Some(self.tree.captures[capture])
+    }
+
+    pub fn parent(&self) -> Option<Node<'t, 'a, K>> {
+        let parent = self.tree.elements[self.id].parent?;
+        Some(Node { tree: self.tree, id: parent })
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = Node<'t, 'a, K>> + use<'t, 'a, K> {
+        let tree = self.tree;
+        let children = match self.data() {
+            Data::Node { children, .. } => children.as_slice(),
+            Data::Leaf(_) => &[],
+        };
+        children.iter().map(move |&id| Node { tree, id })
+    }
+
+    // input this node was parsed from; all of it for root, unless lexer had skip rules
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.write(&mut text);
+        text
+    }
+
+    fn write(&self, text: &mut String) {
+        match self.data() {
+            Data::Node { .. } => self.children().for_each(|child| child.write(text)),
+            Data::Leaf(token) => {
+                token.leading().iter().for_each(|trivia| text.push_str(trivia.value()));
+                text.push_str(token.value());
+                token.trailing().iter().for_each(|trivia| text.push_str(trivia.value()));
+            }
+        }
+    }
+}
+
+// elements symbol of parser stack stands for
+enum Entry {
+    // token or node of rule
+    One(usize),
+    // children of helper nonterminal, to be spliced into node of rule
+    Spliced(Vec<usize>),
+}
+
+// tree from shifts and reductions
+pub(crate) struct Builder<'p, 'a, K: 'static> {
+    parser: &'p Parser<K>,
+    elements: Vec<Element<'a, K>>,
+    stack: Vec<Entry>,
+}
+
+impl<'p, 'a, K> Builder<'p, 'a, K> {
+    pub(crate) fn new(parser: &'p Parser<K>) -> Self {
+        Self {
+            parser,
+            elements: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    // on accept, start symbol is only one left
+    pub(crate) fn finish(self) -> Tree<'a, K> {
+        let Some(&Entry::One(root)) = self.stack.first() else {
+            unreachable!("start symbol is not helper");
+        };
+
+        Tree {
+            elements: self.elements,
+            root,
+            captures: self.parser.captures,
+        }
+    }
+}
+
+impl<'a, K> Sink<'a, K> for Builder<'_, 'a, K> {
+    fn shift(&mut self, token: Token<'a, K>) {
+        self.stack.push(Entry::One(self.elements.len()));
+        self.elements.push(Element {
+            parent: None,
+            capture: None,
+            data: Data::Leaf(token),
+        });
+    }
+
+    // left out of tree
+    fn discard(&mut self, len: usize) {
+        self.stack.truncate(self.stack.len() - len);
+    }
+
+    fn reduce(&mut self, production: usize) {
+        let Production { head, len, captures } = self.parser.productions[production];
+
+        // spliced ones were captured by helper already
+        let mut children = Vec::new();
+        for (entry, &capture) in self.stack.drain(self.stack.len() - len..).zip(captures) {
+            match entry {
+                Entry::One(id) => {
+                    self.elements[id].capture = capture;
+                    children.push(id);
+                }
+                Entry::Spliced(ids) => children.extend(ids),
+            }
+        }
+
+        if self.parser.nonterminals[head].helper {
+            self.stack.push(Entry::Spliced(children));
+            return;
+        }
+
+        let id = self.elements.len();
+        for &child in &children {
+            self.elements[child].parent = Some(id);
+        }
+        self.elements.push(Element {
+            parent: None,
+            capture: None,
+            data: Data::Node {
+                nonterminal: head,
+                production,
+                children,
+            },
+        });
+        self.stack.push(Entry::One(id));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lex::{Channel, Dfa, Lexer, Rule, Transition};
+    use crate::parse::{Action, Nonterminal, Table};
+
+    // A : 'a' ; B : 'b' ; hidden ' '
+    static LEXER: Lexer = Lexer {
+        rules: &[
+            Rule { id: 0, channel: Channel::Token, mode: None },
+            Rule { id: 1, channel: Channel::Token, mode: None },
+            Rule { id: 2, channel: Channel::Hidden, mode: None },
+        ],
+        modes: &[Dfa {
+            offsets: &[0, 3, 3, 3, 3],
+            transitions: &[
+                Transition { range: ' '..=' ', target: 3 },
+                Transition { range: 'a'..='a', target: 1 },
+                Transition { range: 'b'..='b', target: 2 },
+            ],
+            accept: &[None, Some(0), Some(1), Some(2)],
+        }],
+        error: 3,
+    };
+
+    // S : A@a* B@b ;
+    // S -> S#1 B@b, S#1 -> S#1 A@a | ()
+    static PARSER: Parser = Parser {
+        terminals: &[None, Some(0), Some(1)],
+        columns: &[Some(1), Some(2)],
+        nonterminals: &[
+            Nonterminal { name: "$accept", helper: false },
+            Nonterminal { name: "S", helper: false },
+            Nonterminal { name: "S#1", helper: true },
+        ],
+        productions: &[
+            Production { head: 0, len: 1, captures: &[None] },
+            Production { head: 1, len: 2, captures: &[None, Some(1)] },
+            Production { head: 2, len: 2, captures: &[None, Some(0)] },
+            Production { head: 2, len: 0, captures: &[] },
+        ],
+        captures: &["a", "b"],
+        error: None,
+        action: Table {
+            rows: &[0, 1, 2, 3, 4],
+            base: &[0, 0, 0, 0, 0],
+            default: &[Action::Reduce(3), Action::Error, Action::Error, Action::Reduce(2), Action::Reduce(1)],
+            check: &[1, 2, 2],
+            entries: &[Action::Accept, Action::Shift(3), Action::Shift(4)],
+        },
+        goto: Table {
+            rows: &[0, 1, 1, 1, 1],
+            base: &[0, 3],
+            default: &[None, None],
+            check: &[1, 0, 0],
+            entries: &[None, Some(1), Some(2)],
+        },
+    };
+
+    #[test]
+    fn test() {
+        let from = " a a  b ";
+        let tree = PARSER.parse_tree(LEXER.lex(from).unwrap()).unwrap();
+        let root = tree.root();
+        assert_eq!((root.nonterminal(), root.production()), (Some(1), Some(1)));
+        assert!(root.parent().is_none() && root.token().is_none());
+        assert_eq!(root.text(), from);
+
+        let values = root.children().map(|child| child.token().unwrap().value()).collect::<Vec<_>>();
+        assert_eq!(values, ["a", "a", "b"]);
+        let captures = root.children().map(|child| child.capture().unwrap()).collect::<Vec<_>>();
+        assert_eq!(captures, ["a", "a", "b"]);
+        assert!(root.capture().is_none());
+        let last = root.children().last().unwrap();
+        assert_eq!(last.parent().unwrap().nonterminal(), Some(1));
+        assert_eq!(last.text(), "  b ");
+        assert_eq!(last.token().unwrap().span(), 6..7);
+        assert_eq!(last.children().count(), 0);
+
+        let tree = PARSER.parse_tree(LEXER.lex("b").unwrap()).unwrap();
+        assert_eq!(tree.root().children().count(), 1);
+        assert_eq!(PARSER.parse_tree(LEXER.lex("a ").unwrap()).err().unwrap().at, 1);
+    }
+}